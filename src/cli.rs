@@ -0,0 +1,95 @@
+use std::{
+    error::Error,
+    fs,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+};
+use structopt::StructOpt;
+
+use crate::config::Options;
+use crate::constants::DEFAULT_TAB_WIDTH;
+use crate::editor::buffer::Buffer;
+use crate::export::{self, Format};
+use crate::utils::junctions::fixup_all;
+
+// no doc comment here, structopt would show it as the about text of unikode itself
+#[derive(Clone, Debug, StructOpt)]
+pub(crate) enum Command {
+    /// Convert a diagram to another format.
+    Convert {
+        /// Diagram to convert, or "-" for stdin.
+        #[structopt(name = "IN")]
+        input: PathBuf,
+
+        /// Output format (text, markdown, svg, html). HTML uses the background and
+        /// color_normal theme colours only.
+        #[structopt(long, default_value = "text")]
+        to: Format,
+
+        /// Where to write the output, instead of stdout.
+        #[structopt(short, long)]
+        out: Option<PathBuf>,
+    },
+
+    /// Repair junctions and strip whitespace margins from diagrams, in place.
+    Normalize {
+        /// Diagrams to normalize, or "-" to read stdin and write stdout.
+        #[structopt(name = "FILES", required = true)]
+        files: Vec<PathBuf>,
+    },
+}
+
+/// Run `cmd` to completion.
+pub(crate) fn run(cmd: &Command, opts: &Options) -> Result<(), Box<dyn Error>> {
+    match cmd {
+        Command::Convert { input, to, out } => {
+            let buf = normalized(load(input, opts)?, opts);
+            let rendered = export::render(&buf, *to, opts);
+            match out {
+                Some(path) => fs::write(path, rendered)?,
+                None => io::stdout().write_all(rendered.as_bytes())?,
+            }
+        }
+
+        Command::Normalize { files } => {
+            for path in files {
+                let buf = normalized(load(path, opts)?, opts);
+                let rendered: String = buf.iter("").collect();
+                if is_stdio(path) {
+                    io::stdout().write_all(rendered.as_bytes())?;
+                } else {
+                    fs::write(path, rendered)?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn is_stdio(path: &Path) -> bool {
+    path.as_os_str() == "-"
+}
+
+/// Read the diagram at `path`, or from stdin if `path` is "-".
+fn load(path: &Path, opts: &Options) -> io::Result<Buffer> {
+    let tab_width = opts.tab_width.unwrap_or(DEFAULT_TAB_WIDTH);
+
+    if is_stdio(path) {
+        let mut bytes = vec![];
+        io::stdin().read_to_end(&mut bytes)?;
+        return Buffer::read_from(&bytes[..], tab_width);
+    }
+
+    let bytes = fs::read(path)
+        .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+    Buffer::read_from(&bytes[..], tab_width)
+}
+
+/// Returns `buf` with junctions repaired and whitespace margins stripped.
+fn normalized(mut buf: Buffer, opts: &Options) -> Buffer {
+    fixup_all(&mut buf, &opts.symbols);
+    buf.flush_edits();
+    buf.strip_margin_whitespace();
+    buf
+}
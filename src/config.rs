@@ -3,6 +3,7 @@ use std::fs;
 use std::io::{self, BufRead};
 use structopt::StructOpt;
 
+use crate::cli::Command;
 use crate::tools::PathMode::*;
 use crate::tools::PathMode;
 use crate::constants::*;
@@ -90,7 +91,6 @@ impl Default for Symbols {
     help_message = "print help information",
     version_message = "print version information"
 )]
-
 pub(crate) struct Options {
     /// How paths are routed.
     #[structopt(skip = PathMode::Snap90)]
@@ -116,6 +116,22 @@ pub(crate) struct Options {
     #[structopt(long)]
     pub show_mode: bool,
 
+    /// Maximum number of edits kept in the undo history.
+    #[structopt(long)]
+    pub undo_depth: Option<usize>,
+
+    /// Persist undo history across sessions (on save).
+    #[structopt(long)]
+    pub persist_undo: bool,
+
+    /// Wrap HTML exports in a collapsible block.
+    #[structopt(long)]
+    pub html_collapsible: bool,
+
+    /// Columns between tab stops when expanding tabs (on load).
+    #[structopt(long)]
+    pub tab_width: Option<usize>,
+
     /// Background color (hex or "transparent").
     #[structopt(long)]
     pub background: Option<String>,
@@ -163,6 +179,10 @@ pub(crate) struct Options {
     /// Text file to operate on.
     #[structopt(name = "FILE")]
     pub file: Option<PathBuf>,
+
+    /// Run a command without starting the editor.
+    #[structopt(subcommand)]
+    pub command: Option<Command>,
 }
 
 pub fn parse_color(s: &str) -> Option<cursive::theme::Color> {
@@ -185,8 +205,11 @@ pub fn parse_color(s: &str) -> Option<cursive::theme::Color> {
 impl Options {
     pub fn cycle_path_mode(&mut self) {
         self.path_mode = match self.path_mode {
-            Routed => Snap90,
-            Snap90 => Routed,
+            Snap90 => Snap45,
+            Snap45 => Free,
+            Free => Routed,
+            Routed => Orthogonal,
+            Orthogonal => Snap90,
         };
     }
 
@@ -202,104 +225,126 @@ impl Options {
             if path.exists() {
                 if let Ok(file) = fs::File::open(&path) {
                     let reader = io::BufReader::new(file);
-                    for line in reader.lines() {
-                        if let Ok(l) = line {
-                            let parts: Vec<&str> = l.splitn(2, '=').map(|s| s.trim()).collect();
-                            if parts.len() == 2 {
-                                match parts[0] {
-                                    "line_mode" => {
-                                        if self.line_mode.is_none() {
-                                            if let Ok(mode) = parts[1].parse() {
-                                                self.line_mode = Some(mode);
-                                            }
+                    for l in reader.lines().map_while(Result::ok) {
+                        let parts: Vec<&str> = l.splitn(2, '=').map(|s| s.trim()).collect();
+                        if parts.len() == 2 {
+                            match parts[0] {
+                                "line_mode" => {
+                                    if self.line_mode.is_none() {
+                                        if let Ok(mode) = parts[1].parse() {
+                                            self.line_mode = Some(mode);
                                         }
                                     }
-                                    "box_cursor_start" => {
+                                }
+                                "box_cursor_start" => {
+                                    if let Ok(val) = parts[1].parse() {
+                                        self.box_cursor_start = val;
+                                    }
+                                }
+                                "show_mode" => {
+                                    if let Ok(val) = parts[1].parse() {
+                                        self.show_mode = val;
+                                    }
+                                }
+                                "html_collapsible" => {
+                                    if let Ok(val) = parts[1].parse() {
+                                        self.html_collapsible = val;
+                                    }
+                                }
+                                "persist_undo" => {
+                                    if let Ok(val) = parts[1].parse() {
+                                        self.persist_undo = val;
+                                    }
+                                }
+                                "undo_depth" => {
+                                    if self.undo_depth.is_none() {
                                         if let Ok(val) = parts[1].parse() {
-                                            self.box_cursor_start = val;
+                                            self.undo_depth = Some(val);
                                         }
                                     }
-                                    "show_mode" => {
+                                }
+                                "tab_width" => {
+                                    if self.tab_width.is_none() {
                                         if let Ok(val) = parts[1].parse() {
-                                            self.show_mode = val;
+                                            self.tab_width = Some(val);
                                         }
                                     }
-                                    "background" => {
-                                        if self.background.is_none() {
-                                            self.background = Some(parts[1].to_string());
-                                        }
+                                }
+                                "background" => {
+                                    if self.background.is_none() {
+                                        self.background = Some(parts[1].to_string());
                                     }
-                                    "color_normal" => {
-                                        if self.color_normal.is_none() {
-                                            self.color_normal = Some(parts[1].to_string());
-                                        }
+                                }
+                                "color_normal" => {
+                                    if self.color_normal.is_none() {
+                                        self.color_normal = Some(parts[1].to_string());
                                     }
-                                    "color_dirty" => {
-                                        if self.color_dirty.is_none() {
-                                            self.color_dirty = Some(parts[1].to_string());
-                                        }
+                                }
+                                "color_dirty" => {
+                                    if self.color_dirty.is_none() {
+                                        self.color_dirty = Some(parts[1].to_string());
                                     }
-                                    "color_dirty_bg" => {
-                                        if self.color_dirty_bg.is_none() {
-                                            self.color_dirty_bg = Some(parts[1].to_string());
-                                        }
+                                }
+                                "color_dirty_bg" => {
+                                    if self.color_dirty_bg.is_none() {
+                                        self.color_dirty_bg = Some(parts[1].to_string());
                                     }
-                                    "color_cursor_fg" => {
-                                        if self.color_cursor_fg.is_none() {
-                                            self.color_cursor_fg = Some(parts[1].to_string());
-                                        }
+                                }
+                                "color_cursor_fg" => {
+                                    if self.color_cursor_fg.is_none() {
+                                        self.color_cursor_fg = Some(parts[1].to_string());
                                     }
-                                    "color_cursor_bg" => {
-                                        if self.color_cursor_bg.is_none() {
-                                            self.color_cursor_bg = Some(parts[1].to_string());
-                                        }
+                                }
+                                "color_cursor_bg" => {
+                                    if self.color_cursor_bg.is_none() {
+                                        self.color_cursor_bg = Some(parts[1].to_string());
                                     }
-                                    "color_selection_fg" => {
-                                        if self.color_selection_fg.is_none() {
-                                            self.color_selection_fg = Some(parts[1].to_string());
-                                        }
+                                }
+                                "color_selection_fg" => {
+                                    if self.color_selection_fg.is_none() {
+                                        self.color_selection_fg = Some(parts[1].to_string());
                                     }
-                                    "color_selection_bg" => {
-                                        if self.color_selection_bg.is_none() {
-                                            self.color_selection_bg = Some(parts[1].to_string());
-                                        }
+                                }
+                                "color_selection_bg" => {
+                                    if self.color_selection_bg.is_none() {
+                                        self.color_selection_bg = Some(parts[1].to_string());
                                     }
-                                    "color_ui" | "color_linenum" => {
-                                        if self.color_ui.is_none() {
-                                            self.color_ui = Some(parts[1].to_string());
-                                        }
+                                }
+                                "color_ui" | "color_linenum" => {
+                                    if self.color_ui.is_none() {
+                                        self.color_ui = Some(parts[1].to_string());
                                     }
-                                    "color_ui_active" | "color_linenum_active" => {
-                                        if self.color_ui_active.is_none() {
-                                            self.color_ui_active = Some(parts[1].to_string());
-                                        }
+                                }
+                                "color_ui_active" | "color_linenum_active" => {
+                                    if self.color_ui_active.is_none() {
+                                        self.color_ui_active = Some(parts[1].to_string());
                                     }
-                                    "symbol_n" => if let Some(c) = parts[1].chars().next() { self.symbols.n = c; }
-                                    "symbol_s" => if let Some(c) = parts[1].chars().next() { self.symbols.s = c; }
-                                    "symbol_w" => if let Some(c) = parts[1].chars().next() { self.symbols.w = c; }
-                                    "symbol_e" => if let Some(c) = parts[1].chars().next() { self.symbols.e = c; }
-                                    "symbol_dash" => if let Some(c) = parts[1].chars().next() { self.symbols.dash = c; }
-                                    "symbol_pipe" => if let Some(c) = parts[1].chars().next() { self.symbols.pipe = c; }
-                                    "symbol_diag" => if let Some(c) = parts[1].chars().next() { self.symbols.diag = c; }
-                                    "symbol_diag2" => if let Some(c) = parts[1].chars().next() { self.symbols.diag2 = c; }
-                                    "symbol_gaid" => if let Some(c) = parts[1].chars().next() { self.symbols.gaid = c; }
-                                    "symbol_gaid2" => if let Some(c) = parts[1].chars().next() { self.symbols.gaid2 = c; }
-                                    "symbol_plus" => if let Some(c) = parts[1].chars().next() { self.symbols.plus = c; }
-                                    "symbol_curs" => if let Some(c) = parts[1].chars().next() { self.symbols.curs = c; }
-                                    "symbol_brcorn" => if let Some(c) = parts[1].chars().next() { self.symbols.brcorn = c; }
-                                    "symbol_blcorn" => if let Some(c) = parts[1].chars().next() { self.symbols.blcorn = c; }
-                                    "symbol_trcorn" => if let Some(c) = parts[1].chars().next() { self.symbols.trcorn = c; }
-                                    "symbol_tlcorn" => if let Some(c) = parts[1].chars().next() { self.symbols.tlcorn = c; }
-                                    "symbol_vline" => if let Some(c) = parts[1].chars().next() { self.symbols.vline = c; }
-                                    "symbol_hline" => if let Some(c) = parts[1].chars().next() { self.symbols.hline = c; }
-                                    "symbol_lhinter" => if let Some(c) = parts[1].chars().next() { self.symbols.lhinter = c; }
-                                    "symbol_rhinter" => if let Some(c) = parts[1].chars().next() { self.symbols.rhinter = c; }
-                                    "symbol_bvinter" => if let Some(c) = parts[1].chars().next() { self.symbols.bvinter = c; }
-                                    "symbol_tvinter" => if let Some(c) = parts[1].chars().next() { self.symbols.tvinter = c; }
-                                    "symbol_cinter" => if let Some(c) = parts[1].chars().next() { self.symbols.cinter = c; }
-                                    "symbol_ubox" => if let Some(c) = parts[1].chars().next() { self.symbols.ubox = c; }
-                                    _ => {}
                                 }
+                                "symbol_n" => if let Some(c) = parts[1].chars().next() { self.symbols.n = c; }
+                                "symbol_s" => if let Some(c) = parts[1].chars().next() { self.symbols.s = c; }
+                                "symbol_w" => if let Some(c) = parts[1].chars().next() { self.symbols.w = c; }
+                                "symbol_e" => if let Some(c) = parts[1].chars().next() { self.symbols.e = c; }
+                                "symbol_dash" => if let Some(c) = parts[1].chars().next() { self.symbols.dash = c; }
+                                "symbol_pipe" => if let Some(c) = parts[1].chars().next() { self.symbols.pipe = c; }
+                                "symbol_diag" => if let Some(c) = parts[1].chars().next() { self.symbols.diag = c; }
+                                "symbol_diag2" => if let Some(c) = parts[1].chars().next() { self.symbols.diag2 = c; }
+                                "symbol_gaid" => if let Some(c) = parts[1].chars().next() { self.symbols.gaid = c; }
+                                "symbol_gaid2" => if let Some(c) = parts[1].chars().next() { self.symbols.gaid2 = c; }
+                                "symbol_plus" => if let Some(c) = parts[1].chars().next() { self.symbols.plus = c; }
+                                "symbol_curs" => if let Some(c) = parts[1].chars().next() { self.symbols.curs = c; }
+                                "symbol_brcorn" => if let Some(c) = parts[1].chars().next() { self.symbols.brcorn = c; }
+                                "symbol_blcorn" => if let Some(c) = parts[1].chars().next() { self.symbols.blcorn = c; }
+                                "symbol_trcorn" => if let Some(c) = parts[1].chars().next() { self.symbols.trcorn = c; }
+                                "symbol_tlcorn" => if let Some(c) = parts[1].chars().next() { self.symbols.tlcorn = c; }
+                                "symbol_vline" => if let Some(c) = parts[1].chars().next() { self.symbols.vline = c; }
+                                "symbol_hline" => if let Some(c) = parts[1].chars().next() { self.symbols.hline = c; }
+                                "symbol_lhinter" => if let Some(c) = parts[1].chars().next() { self.symbols.lhinter = c; }
+                                "symbol_rhinter" => if let Some(c) = parts[1].chars().next() { self.symbols.rhinter = c; }
+                                "symbol_bvinter" => if let Some(c) = parts[1].chars().next() { self.symbols.bvinter = c; }
+                                "symbol_tvinter" => if let Some(c) = parts[1].chars().next() { self.symbols.tvinter = c; }
+                                "symbol_cinter" => if let Some(c) = parts[1].chars().next() { self.symbols.cinter = c; }
+                                "symbol_ubox" => if let Some(c) = parts[1].chars().next() { self.symbols.ubox = c; }
+                                _ => {}
                             }
                         }
                     }
@@ -311,6 +356,12 @@ impl Options {
         if self.line_mode.is_none() {
             self.line_mode = Some(LineNumberMode::Relative);
         }
+        if self.undo_depth.is_none() {
+            self.undo_depth = Some(DEFAULT_UNDO_DEPTH);
+        }
+        if self.tab_width.is_none() {
+            self.tab_width = Some(DEFAULT_TAB_WIDTH);
+        }
         if self.background.is_none() {
             self.background = Some(DEFAULT_BACKGROUND.to_string());
         }
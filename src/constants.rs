@@ -3,11 +3,9 @@ use cursive::{
     event::EventResult,
 };
 
-use std::f64::consts::SQRT_2;
+use std::{f64::consts::SQRT_2, time::Duration};
 
 pub(crate) const EDITOR_ID: &str = "editor";
-pub(crate) const S90: &str = "Snap90";
-pub(crate) const RTD: &str = "Routed";
 
 pub(crate) const N: char = '▲';
 pub(crate) const S: char = '▼';
@@ -23,6 +21,12 @@ pub(crate) const S_W: (isize, isize) = (-1, 0);
 pub(crate) const D: f64 = 1.0;
 /// Cost to move one step on the diagonal plane.
 pub(crate) const D2: f64 = SQRT_2;
+/// Extra cost for each change of direction along an orthogonal route.
+pub(crate) const BEND: f64 = 4.0;
+/// Cells of padding around the buffer that routes may pass through.
+pub(crate) const ROUTE_MARGIN: usize = 8;
+/// Number of cells a route search may expand before giving up.
+pub(crate) const ROUTE_BUDGET: usize = 50_000;
 
 pub(crate) const SP: char = ' ';
 pub(crate) const DASH: char = '-';
@@ -63,12 +67,18 @@ pub(crate) const POPUP_ID: &str = "generic_popup";
 pub(crate) const INPUT_ID: &str = "generic_input";
 
 pub(crate) const KEY_UNDO: char = 'u';
+pub(crate) const KEY_UNDO_OLDER: char = '-';
+pub(crate) const KEY_UNDO_NEWER: char = '+';
+pub(crate) const KEY_UNDO_LIST: char = 'U';
 pub(crate) const KEY_SAVE: char = 'w';
 pub(crate) const KEY_SAVE_AS: char = 'S';
 pub(crate) const KEY_CLIP: char = 'c';
 pub(crate) const KEY_CLIP_PREFIX: char = 'C';
+pub(crate) const KEY_EXPORT_SVG: char = 'V';
+pub(crate) const KEY_EXPORT_HTML: char = 'H';
 pub(crate) const KEY_NEW: char = 'n';
 pub(crate) const KEY_OPEN: char = 'o';
+pub(crate) const KEY_OPEN_BLOCK: char = 'O';
 pub(crate) const KEY_QUIT: char = 'q';
 pub(crate) const KEY_DEBUG: char = '`';
 pub(crate) const KEY_CYCLE_PATH: char = 'p';
@@ -88,12 +98,41 @@ pub(crate) const KEY_TOOL_BOX: char = 'b';
 pub(crate) const KEY_TOOL_LINE: char = 'L';
 pub(crate) const KEY_TOOL_ARROW: char = 'a';
 pub(crate) const KEY_TOOL_TEXT: char = 't';
+pub(crate) const KEY_TOOL_LABEL: char = 'B';
+pub(crate) const KEY_TOOL_POLYLINE: char = 'W';
 pub(crate) const KEY_TOOL_SELECT: char = 's';
 pub(crate) const KEY_TOOL_ERASE: char = 'e';
 pub(crate) const KEY_TOOL_MOVE: char = 'm';
+pub(crate) const KEY_TOOL_MOVE_CONNECTED: char = 'M';
+pub(crate) const KEY_DUPLICATE: char = 'd';
+pub(crate) const KEY_ROTATE: char = 'r';
+pub(crate) const KEY_FLIP_H: char = 'f';
+pub(crate) const KEY_FLIP_V: char = 'F';
+pub(crate) const KEY_FLIP_BEND: char = 'f';
+pub(crate) const KEY_YANK: char = 'y';
+pub(crate) const KEY_SELECT_SHAPE: char = 'v';
+pub(crate) const KEY_DELETE_SHAPE: char = 'x';
+pub(crate) const KEY_RESIZE: char = 'R';
+pub(crate) const KEY_PUT: char = 'P';
+pub(crate) const KEY_REGISTER: char = '"';
+
+/// Register used when no `"x` prefix is given.
+pub(crate) const UNNAMED_REGISTER: char = '"';
 
 pub(crate) const GUTTER_WIDTH: usize = 5;
 
+/// Longest gap between two clicks in the same place for them to count as a double click.
+pub(crate) const DOUBLE_CLICK: Duration = Duration::from_millis(400);
+
+/// Blank columns between a label and the sides of its box.
+pub(crate) const LABEL_PADDING: usize = 1;
+
+/// Number of edits kept in the undo history unless configured otherwise.
+pub(crate) const DEFAULT_UNDO_DEPTH: usize = 1000;
+
+/// Columns between tab stops when expanding tabs on load, unless configured otherwise.
+pub(crate) const DEFAULT_TAB_WIDTH: usize = 8;
+
 // Default Colours
 pub(crate) const DEFAULT_BACKGROUND: &str = "#212121";
 pub(crate) const DEFAULT_COLOR_NORMAL: &str = "#ffffff";
@@ -1,6 +1,10 @@
 pub mod buffer;
 pub mod cell;
+pub mod embed;
+pub mod grid;
+pub mod history;
 pub mod scroll;
+pub mod undofile;
 
 use clipboard::{ClipboardContext, ClipboardProvider};
 use cursive::{
@@ -8,13 +12,14 @@ use cursive::{
     view::View,
     Printer, Vec2, Rect,
 };
+use log::warn;
 use parking_lot::{RwLock, RwLockReadGuard, RwLockWriteGuard};
 use std::{
     cmp::max,
+    collections::HashMap,
     error::Error,
     fs::{self, File, OpenOptions},
     io::{self, ErrorKind, Write},
-    mem,
     path::{Path, PathBuf},
     sync::Arc,
     fmt,
@@ -22,14 +27,28 @@ use std::{
 
 use crate::editor::{
     buffer::Buffer,
-    cell::{Cell, Char},
+    cell::{Cell, Char, Glyph},
+    embed::Embed,
+    history::{History, UndoState},
 };
 use crate::tools::{
-    Tool,
-    lines::boxtool::BoxTool
+    Bend, Tool,
+    lines::boxtool::BoxTool,
+    movetool::{stamp_on_buffer, MoveTool},
+    resizetool::ResizeTool,
+    erasetool::erase_shape_on_buffer,
+    selecttool::SelectTool,
+    visible_cells,
 };
 use crate::config::{Options, LineNumberMode};
-use crate::constants::GUTTER_WIDTH;
+use crate::export::{self, Format};
+use crate::constants::{
+    GUTTER_WIDTH, DEFAULT_TAB_WIDTH, DEFAULT_UNDO_DEPTH, SP, UNNAMED_REGISTER,
+};
+use crate::utils::{
+    junctions::{connects_down, connects_left, connects_right, connects_up},
+    shapes::{Label, Shapes},
+};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum EditorMode {
@@ -39,7 +58,8 @@ pub enum EditorMode {
     Line(Vec2),
     Arrow(Vec2),
     Select(Vec2),
-    Move { selection: Rect, anchor: Vec2 },
+    Resize,
+    Polyline,
 }
 
 impl fmt::Display for EditorMode {
@@ -51,7 +71,8 @@ impl fmt::Display for EditorMode {
             EditorMode::Line(_) => write!(f, "LINE"),
             EditorMode::Arrow(_) => write!(f, "ARROW"),
             EditorMode::Select(_) => write!(f, "SELECT"),
-            EditorMode::Move { .. } => write!(f, "MOVE"),
+            EditorMode::Resize => write!(f, "RESIZE"),
+            EditorMode::Polyline => write!(f, "POLYLINE"),
         }
     }
 }
@@ -88,7 +109,7 @@ impl View for EditorView {
             _ => None,
         };
 
-        let is_moving = editor.active_tool.as_ref().map_or(false, |t| t.move_info().is_some());
+        let is_moving = editor.active_tool.as_ref().is_some_and(|t| t.move_info().is_some());
 
         // Draw line numbers (sticky on the left)
         for y in 0..p.size.y {
@@ -118,9 +139,9 @@ impl View for EditorView {
 
         for c in editor.buffer.iter_within(content_offset, content_size, &editor.opts.symbols) {
             let (pos, char_val, is_cursor, is_dirty) = match c {
-                Char::Clean(Cell { pos, c }) => (pos, c, false, false),
-                Char::Dirty(Cell { pos, c }) => (pos, c, false, true),
-                Char::Cursor(Cell { pos, c }) => (pos, c, true, false),
+                Char::Clean(Cell { pos, glyph }) => (pos, glyph, false, false),
+                Char::Dirty(Cell { pos, glyph }) => (pos, glyph, false, true),
+                Char::Cursor(Cell { pos, glyph }) => (pos, glyph, true, false),
             };
 
             let view_pos = pos.map_x(|x| x + GUTTER_WIDTH);
@@ -137,11 +158,19 @@ impl View for EditorView {
                 continue;
             }
 
+            // The second column of a double width cell is drawn along with the first
+            let char_val = match char_val {
+                Glyph::Wide if is_cursor => Glyph::Char(SP),
+                Glyph::Wide => continue,
+                g => g,
+            };
+            let blank = char_val == Glyph::Char(' ');
+
             if is_cursor {
                 cursor(p, view_pos, char_val);
-            } else if should_highlight && char_val != ' ' {
+            } else if should_highlight && !blank {
                 selection_style(p, view_pos, char_val);
-            } else if !is_moving && is_dirty && char_val != ' ' {
+            } else if !is_moving && is_dirty && !blank {
                 change(p, view_pos, char_val);
             } else {
                 normal(p, view_pos, char_val);
@@ -181,13 +210,26 @@ impl EditorView {
 pub(crate) struct Editor {
     pub(crate) mode: EditorMode,
     pub(crate) pending_count: String,
+    /// Register named by a `"x` prefix, used by the next yank or put.
+    pub(crate) register: Option<char>,
+    /// Set after `"`, while waiting for the register name.
+    pub(crate) awaiting_register: bool,
+    /// Yanked cells by register name, positioned relative to the top left of what was
+    /// yanked.
+    registers: HashMap<char, Vec<Cell>>,
     pub(crate) opts: Options,
     pub(crate) buffer: Buffer,
     lsave: Buffer,
+    /// Where the buffer lives within the file at `opts.file`, if it is only part of it.
+    embed: Option<Embed>,
     dirty: bool,
-    undo_history: Vec<Buffer>,
-    redo_history: Vec<Buffer>,
+    history: History,
     pub(crate) active_tool: Option<Box<dyn Tool + Send + Sync>>,
+    /// Which way Snap90 lines from a start point bend, once they have left it.
+    bend: Option<(Vec2, Bend)>,
+    /// Where the left button went down in Normal mode, until it is released. Dragging
+    /// away from there grabs a box outline for resizing.
+    pub(crate) press: Option<Vec2>,
     rendered: String,
 }
 
@@ -199,16 +241,23 @@ impl Editor {
         let mut tool = BoxTool::default();
         tool.load_opts(&opts);
 
+        let history = History::new(opts.undo_depth.unwrap_or(DEFAULT_UNDO_DEPTH));
+
         let mut editor = Self {
             mode: EditorMode::Normal,
             pending_count: String::new(),
+            register: None,
+            awaiting_register: false,
+            registers: HashMap::new(),
             opts,
             buffer: Buffer::default(),
             lsave: Buffer::default(),
+            embed: None,
             dirty: false,
-            undo_history: vec![],
-            redo_history: vec![],
+            history,
             active_tool: Some(Box::new(tool)),
+            bend: None,
+            press: None,
             rendered: String::default(),
         };
 
@@ -235,10 +284,37 @@ impl Editor {
     /// Set the active tool.
     pub(crate) fn set_tool<T: Tool + 'static + Send + Sync>(&mut self, mut tool: T) {
         self.buffer.discard_edits();
+        self.forget_bend();
         tool.load_opts(&self.opts);
         self.active_tool = Some(Box::new(tool));
     }
 
+    /// Returns which way a Snap90 line from `src` to `dst` bends. Until it is flipped,
+    /// a line follows the direction it first moved away from `src` in.
+    pub(crate) fn bend(&mut self, src: Vec2, dst: Vec2) -> Bend {
+        match self.bend {
+            Some((start, bend)) if start == src => bend,
+            _ if src == dst => Bend::default(),
+            _ => {
+                let bend = Bend::towards(src, dst);
+                self.bend = Some((src, bend));
+                bend
+            }
+        }
+    }
+
+    /// Switch which way the Snap90 line from `src` to `dst` bends.
+    pub(crate) fn flip_bend(&mut self, src: Vec2, dst: Vec2) {
+        let bend = self.bend(src, dst).flip();
+        self.bend = Some((src, bend));
+    }
+
+    /// Forget the bend of the last line, once it has been committed or discarded, so
+    /// that the next line from the same start point picks its own.
+    pub(crate) fn forget_bend(&mut self) {
+        self.bend = None;
+    }
+
     /// Returns the active tool as a human readable string.
     pub(crate) fn active_tool(&self) -> String {
         format!("({})", self.active_tool.as_ref().unwrap())
@@ -254,31 +330,53 @@ impl Editor {
         self.opts.file.as_ref()
     }
 
+    /// Returns the current save path as a human readable string, with the line the
+    /// diagram starts at if it is embedded in a larger file.
+    pub(crate) fn location(&self) -> Option<String> {
+        let path = self.path()?.display();
+
+        Some(match &self.embed {
+            Some(embed) => format!("{}:{}", path, embed.line),
+            None => path.to_string(),
+        })
+    }
+
     /// Clear all buffer state and begin a blank diagram.
     pub(crate) fn clear(&mut self) {
         self.opts.file = None;
         self.buffer.clear();
         self.lsave.clear();
+        self.embed = None;
         self.dirty = false;
-        self.undo_history.clear();
-        self.redo_history.clear();
+        self.history.clear();
     }
 
     /// Open the file at `path`, discarding any unsaved changes to the current file, if
     /// there are any.
     ///
+    /// If undo persistence is enabled and the file is unchanged since it was last saved,
+    /// the undo history from that session is restored.
+    ///
+    /// A path of the form `file.md:LINE` opens only the diagram at that line, which is
+    /// either a fenced block or a run of comment lines. See [Editor::open_embedded].
+    ///
     /// No modifications have been performed if this returns `Err(_) `.
     pub(crate) fn open_file<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
-        let buffer = OpenOptions::new()
-            .read(true)
-            .open(path.as_ref())
-            .and_then(Buffer::read_from);
+        if let (path, Some(line)) = embed::split_location(path.as_ref()) {
+            return self.open_embedded(path, line);
+        }
 
-        let buffer = match buffer {
+        let bytes = match fs::read(path.as_ref()) {
             Err(e) if e.kind() == ErrorKind::NotFound => None,
             r => Some(r?),
         };
 
+        let tab_width = self.opts.tab_width.unwrap_or(DEFAULT_TAB_WIDTH);
+        let buffer = bytes
+            .as_deref()
+            .map(|b| Buffer::read_from(b, tab_width))
+            .transpose()?;
+
         self.clear();
         self.opts.file = Some(path.as_ref().into());
         if let Some(buf) = buffer {
@@ -286,9 +384,82 @@ impl Editor {
             self.buffer = buf;
         }
 
+        if let Some(bytes) = bytes {
+            self.restore_history(undofile::content_hash(&bytes));
+        }
+
         Ok(())
     }
 
+    /// Open the diagram at line `line` (starting at 1) of the file at `path`, discarding
+    /// any unsaved changes to the current file.
+    ///
+    /// The diagram is the fenced block or run of comment lines containing that line, and
+    /// is spliced back into the file on save. Comment prefixes like `// ` are stripped
+    /// on load and put back on save. Undo history is not persisted for embedded diagrams.
+    ///
+    /// No modifications have been performed if this returns `Err(_) `.
+    pub(crate) fn open_embedded<P: AsRef<Path>>(&mut self, path: P, line: usize) -> io::Result<()> {
+        let host = fs::read_to_string(path.as_ref())?;
+
+        let markdown = embed::is_markdown(path.as_ref());
+        let (embed, region) = Embed::find(&host, line, markdown).ok_or_else(|| {
+            let msg = format!("no fenced block or comment block at line {}", line);
+            io::Error::new(ErrorKind::InvalidInput, msg)
+        })?;
+
+        let tab_width = self.opts.tab_width.unwrap_or(DEFAULT_TAB_WIDTH);
+        let buf = Buffer::read_from(region.as_bytes(), tab_width)?;
+
+        self.clear();
+        self.opts.file = Some(path.as_ref().into());
+        self.embed = Some(embed);
+        self.lsave = buf.clone();
+        self.buffer = buf;
+
+        Ok(())
+    }
+
+    /// Load the persisted undo history for the current path, if it was saved for file
+    /// contents matching `hash`.
+    fn restore_history(&mut self, hash: u64) {
+        if !self.opts.persist_undo || self.embed.is_some() {
+            return;
+        }
+
+        let path = match self.path().and_then(|p| undofile::undo_file_path(p)) {
+            Some(p) => p,
+            None => return,
+        };
+
+        let depth = self.opts.undo_depth.unwrap_or(DEFAULT_UNDO_DEPTH);
+        match undofile::read(&path, hash, depth) {
+            Ok(Some(history)) => self.history = history,
+            Ok(None) => {}
+            Err(e) => warn!("failed to read undo file {:?}: {}", path, e),
+        }
+    }
+
+    /// Persist the undo history for the current path, tagged with the hash of the
+    /// contents that were just saved.
+    ///
+    /// Failures are logged rather than returned, as they should not fail the save.
+    fn persist_history(&self) {
+        if !self.opts.persist_undo || self.embed.is_some() {
+            return;
+        }
+
+        let path = match self.path().and_then(|p| undofile::undo_file_path(p)) {
+            Some(p) => p,
+            None => return,
+        };
+
+        let hash = undofile::content_hash(self.rendered.as_bytes());
+        if let Err(e) = undofile::write(&path, hash, &self.history) {
+            warn!("failed to write undo file {:?}: {}", path, e);
+        }
+    }
+
     /// Save the current buffer contents to disk.
     ///
     /// Returns `Ok(true)` if the buffer was saved, and `Ok(false)` if there is no path
@@ -309,6 +480,7 @@ impl Editor {
             self.render_to_file(file)?;
             self.lsave = self.buffer.clone();
             self.dirty = false;
+            self.persist_history();
         }
 
         Ok(self.path().is_some())
@@ -316,16 +488,20 @@ impl Editor {
 
     /// Save the current buffer contents to the file at `path`, and setting that as the
     /// new path for future calls to `save`.
+    ///
+    /// An embedded diagram is saved on its own, and no longer spliced into its host file.
     pub(crate) fn save_as<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
         self.opts.file = Some(path.as_ref().into());
+        self.embed = None;
         self.save()?;
 
         Ok(())
     }
 
-    /// Render to `file`, performing whitespace cleanup if enabled.
+    /// Render to `file`, performing whitespace cleanup if enabled. An embedded diagram is
+    /// spliced back into the rest of its host file.
     fn render_to_file(&mut self, mut file: File) -> io::Result<()> {
-        self.with_snapshot(|ed| {
+        self.with_history("Strip whitespace", |ed| {
             if ed.opts.strip_margin_ws {
                 ed.buffer.strip_margin_whitespace();
             } else if !ed.opts.keep_trailing_ws {
@@ -335,6 +511,9 @@ impl Editor {
 
         self.rendered.clear();
         self.rendered.extend(self.buffer.iter(""));
+        if let Some(embed) = &self.embed {
+            self.rendered = embed.splice(&self.rendered);
+        }
 
         file.write_all(self.rendered.as_bytes())?;
         file.flush()?;
@@ -353,38 +532,233 @@ impl Editor {
         buf.strip_margin_whitespace();
 
         let mut rendered: String = buf.iter(prefix).collect();
-        if let Some(c) = rendered.pop() {
-            if c != '\n' {
-                rendered.push(c);
-            }
+        let ending = buf.line_ending().as_str();
+        if rendered.ends_with(ending) {
+            rendered.truncate(rendered.len() - ending.len());
         }
 
         ctx.set_contents(rendered)
     }
 
+    /// Paste the text on the clipboard into a floating layer at the cursor, which can be
+    /// moved around before it is committed.
+    ///
+    /// A comment or quote prefix shared by every line, such as `// ` or `> `, is
+    /// stripped. Returns `Ok(false)` if there is nothing to paste.
+    pub(crate) fn paste_from_clipboard(&mut self) -> Result<bool, Box<dyn Error>> {
+        let mut ctx = ClipboardContext::new()?;
+        let text = ctx.get_contents()?;
+
+        Ok(self.float_text(&text, "Paste"))
+    }
+
+    /// Drop `text` into a floating layer at the cursor, with its margins trimmed. Once
+    /// committed, it is recorded in the undo history as `desc`.
+    ///
+    /// Returns `false` if `text` has nothing visible in it.
+    pub(crate) fn float_text(&mut self, text: &str, desc: &'static str) -> bool {
+        let text = embed::strip_comment_prefix(text);
+        let tab_width = self.opts.tab_width.unwrap_or(DEFAULT_TAB_WIDTH);
+
+        let mut buf = Buffer::read_from(text.as_bytes(), tab_width).unwrap_or_default();
+        buf.strip_margin_whitespace();
+
+        let cursor = self.buffer.get_cursor().unwrap_or_default();
+        let cells: Vec<_> = buf
+            .iter_within(Vec2::zero(), buf.bounds(), &self.opts.symbols)
+            .filter_map(|c| match c {
+                Char::Clean(cell) if !cell.is_whitespace() => Some(cell.translate(cursor.signed())),
+                _ => None,
+            })
+            .collect();
+
+        self.float(cells, desc)
+    }
+
+    /// Drop `cells` into a floating layer that is dragged around by the cursor. Once
+    /// committed, it is recorded in the undo history as `desc`.
+    ///
+    /// Returns `false` if there are no cells.
+    fn float(&mut self, cells: Vec<Cell>, desc: &'static str) -> bool {
+        if cells.is_empty() {
+            return false;
+        }
+
+        let cursor = self.buffer.get_cursor().unwrap_or_default();
+        self.mode = EditorMode::Select(cursor);
+        self.set_tool(MoveTool::floating(cells.clone(), cursor, desc));
+        stamp_on_buffer(&mut self.buffer, &cells, cursor, cursor);
+
+        true
+    }
+
+    /// Copy the visible cells within `area` into the register named by the pending `"x`
+    /// prefix, without changing the buffer. Yanks always update the unnamed register as
+    /// well.
+    pub(crate) fn yank(&mut self, area: Rect) {
+        let name = self.register.take().unwrap_or(UNNAMED_REGISTER);
+
+        let offset = area.top_left().signed().map(|v| -v);
+        let cells: Vec<_> = visible_cells(&self.buffer, (area.top_left(), area.bottom_right()), &self.opts.symbols)
+            .map(|cell| cell.translate(offset))
+            .collect();
+
+        self.registers.insert(UNNAMED_REGISTER, cells.clone());
+        self.registers.insert(name, cells);
+    }
+
+    /// Put the contents of the register named by the pending `"x` prefix at the cursor,
+    /// as a floating layer that can be moved around before it is committed.
+    ///
+    /// Returns `false` if the register is empty.
+    pub(crate) fn put(&mut self) -> bool {
+        let name = self.register.take().unwrap_or(UNNAMED_REGISTER);
+        let cursor = self.buffer.get_cursor().unwrap_or_default().signed();
+
+        let cells = match self.registers.get(&name) {
+            Some(cells) => cells.iter().map(|cell| cell.clone().translate(cursor)).collect(),
+            None => vec![],
+        };
+
+        self.float(cells, "Put")
+    }
+
+    /// Drag a copy of the visible cells within `area` around by the cursor, leaving the
+    /// originals in place.
+    ///
+    /// Returns `false` if there is nothing to duplicate.
+    pub(crate) fn duplicate(&mut self, area: Rect) -> bool {
+        let cells = visible_cells(&self.buffer, (area.top_left(), area.bottom_right()), &self.opts.symbols).collect();
+
+        self.float(cells, "Duplicate")
+    }
+
+    /// Select the label or box under the cursor or, if `within` is given, the smallest
+    /// box around it.
+    ///
+    /// Returns `false` if there is nothing to select.
+    pub(crate) fn select_shape(&mut self, within: Option<Rect>) -> bool {
+        let cursor = self.buffer.get_cursor().unwrap_or_default();
+        let shapes = Shapes::scan(&self.buffer, &self.opts.symbols);
+
+        let rect = match within {
+            Some(area) => shapes.box_around(area).map(|b| b.rect),
+            None => shapes
+                .label_at(cursor)
+                .map(Label::rect)
+                .or_else(|| shapes.box_at(cursor).map(|b| b.rect)),
+        };
+
+        let rect = match rect {
+            Some(rect) => rect,
+            None => return false,
+        };
+
+        self.mode = EditorMode::Select(rect.top_left());
+        self.set_tool(SelectTool::default());
+        self.buffer.set_cursor(rect.bottom_right());
+
+        true
+    }
+
+    /// Start resizing the box under the cursor, by the edge or corner the cursor is on.
+    /// With `on_outline`, the cursor has to be on the box's outline.
+    ///
+    /// Returns `false` if there is no box to resize.
+    pub(crate) fn resize_box(&mut self, on_outline: bool) -> bool {
+        let cursor = self.buffer.get_cursor().unwrap_or_default();
+
+        // every drag starts here, so rule out anything that can't be an outline before
+        // scanning the whole buffer for boxes
+        let c = self.buffer.get_char_at(cursor);
+        let joins = [connects_up, connects_down, connects_left, connects_right];
+        if on_outline && !joins.iter().any(|joins| joins(c, &self.opts.symbols)) {
+            return false;
+        }
+
+        let shapes = Shapes::scan(&self.buffer, &self.opts.symbols);
+
+        let shape = match shapes.box_at(cursor) {
+            Some(shape) if !(on_outline && shape.inside(cursor)) => *shape,
+            _ => return false,
+        };
+
+        let tool = ResizeTool::grab(shape, cursor);
+        self.buffer.set_cursor(tool.handle(cursor));
+        self.mode = EditorMode::Resize;
+        self.set_tool(tool);
+
+        true
+    }
+
+    /// Delete the line, label or box under the cursor. Deleting a box removes its
+    /// outline and the labels inside it, but keeps any nested boxes and lines.
+    ///
+    /// Returns `false` if there is nothing to delete.
+    pub(crate) fn delete_shape(&mut self) -> bool {
+        let cursor = self.buffer.get_cursor().unwrap_or_default();
+        let shapes = Shapes::scan(&self.buffer, &self.opts.symbols);
+
+        let (desc, cells, joins): (_, Vec<_>, Vec<_>) = if let Some(line) = shapes.line_at(cursor) {
+            ("Delete Line", line.cells.clone(), line.ends.iter().map(|&(pos, _)| pos).collect())
+        } else if let Some(label) = shapes.label_at(cursor) {
+            let rect = label.rect();
+            let cells = (rect.left()..=rect.right()).map(|x| Vec2::new(x, rect.top())).collect();
+            ("Delete Label", cells, vec![])
+        } else if let Some(i) = shapes.box_at(cursor).and_then(|b| shapes.boxes.iter().position(|o| o == b)) {
+            let outline: Vec<_> = shapes.boxes[i].outline().collect();
+            let labels = shapes.labels_in(i).flat_map(|label| {
+                let rect = label.rect();
+                (rect.left()..=rect.right()).map(move |x| Vec2::new(x, rect.top()))
+            });
+            ("Delete Box", outline.iter().copied().chain(labels).collect(), outline)
+        } else {
+            return false;
+        };
+
+        let symbols = self.opts.symbols.clone();
+        self.with_history(desc, |ed| {
+            erase_shape_on_buffer(&mut ed.buffer, &cells, &joins, &symbols);
+            ed.buffer.set_cursor(cursor);
+            ed.buffer.flush_edits();
+        });
+
+        true
+    }
+
+    /// Export to the file at `path` as `format`.
+    ///
+    /// Trims all margins in the output without changing the buffer's state.
+    pub(crate) fn export_to<P: AsRef<Path>>(&self, path: P, format: Format) -> io::Result<()> {
+        let mut buf = self.buffer.clone();
+        buf.strip_margin_whitespace();
+
+        fs::write(path, export::render(&buf, format, &self.opts))
+    }
+
     /// Trim all whitespace from margins.
     pub(crate) fn trim_margins(&mut self) {
-        self.with_snapshot(|ed| {
+        self.with_history("Trim margins", |ed| {
             ed.buffer.strip_margin_whitespace();
             ed.buffer.set_cursor(Vec2::new(0, 0));
         });
     }
 
-    /// Take a snapshot of the buffer, discard any pending edits, and run `apply`. If
-    /// the buffer was modified, mark it as dirty. Otherwise, remove the snapshot.
+    /// Discard any pending edits and run `apply`, recording every modification it makes
+    /// to the buffer as a single step in the undo history, described by `desc`. If the
+    /// buffer was modified, mark it as dirty.
     ///
     /// Use this function to execute any buffer modification that should be saved in the
     /// undo history.
-    fn with_snapshot<F: FnOnce(&mut Self)>(&mut self, apply: F) {
-        let snapshot = self.buffer.snapshot();
-        self.undo_history.push(snapshot);
+    pub(crate) fn with_history<F: FnOnce(&mut Self)>(&mut self, desc: &str, apply: F) {
         self.buffer.discard_edits();
+        self.buffer.begin_delta();
 
         apply(self);
 
-        if self.undo_history.last().unwrap() == &self.buffer {
-            self.undo_history.pop();
-        } else {
+        let delta = self.buffer.end_delta();
+        if !delta.is_empty() {
+            self.history.push(delta, desc);
             self.dirty = true;
         }
     }
@@ -393,54 +767,61 @@ impl Editor {
     ///
     /// Returns `false` if there was nothing to undo.
     pub(crate) fn undo(&mut self) -> bool {
-        let cursor = self.buffer.get_cursor();
-        let undone = self
-            .undo_history
-            .pop()
-            .map(|buffer| mem::replace(&mut self.buffer, buffer))
-            .map(|buffer| self.redo_history.push(buffer))
-            .is_some();
-
-        if undone {
-            self.dirty = self.buffer != self.lsave;
-            if self.buffer.get_cursor().is_none() {
-                if let Some(p) = cursor {
-                    self.buffer.set_cursor(p);
-                }
-            }
-        }
-
-        undone
+        self.walk_history(History::undo)
     }
 
     /// Redo the last undone buffer modification.
     ///
     /// Returns `false` if there was nothing to redo.
     pub(crate) fn redo(&mut self) -> bool {
-        let cursor = self.buffer.get_cursor();
-        let redone = self
-            .redo_history
-            .pop()
-            .map(|buffer| mem::replace(&mut self.buffer, buffer))
-            .map(|buffer| self.undo_history.push(buffer))
-            .is_some();
-
-        if redone {
-            self.dirty = self.buffer != self.lsave;
-            if self.buffer.get_cursor().is_none() {
-                if let Some(p) = cursor {
-                    self.buffer.set_cursor(p);
-                }
-            }
+        self.walk_history(History::redo)
+    }
+
+    /// Move to the chronologically previous buffer state, on any branch of the undo tree.
+    ///
+    /// Returns `false` if there is no older state.
+    pub(crate) fn undo_older(&mut self) -> bool {
+        self.walk_history(History::older)
+    }
+
+    /// Move to the chronologically next buffer state, on any branch of the undo tree.
+    ///
+    /// Returns `false` if there is no newer state.
+    pub(crate) fn undo_newer(&mut self) -> bool {
+        self.walk_history(History::newer)
+    }
+
+    /// Move to the buffer state numbered `seq` in the undo tree.
+    ///
+    /// Returns `false` if there is no such state.
+    pub(crate) fn undo_jump(&mut self, seq: usize) -> bool {
+        self.walk_history(|h, buf| h.jump(buf, seq))
+    }
+
+    /// Returns every state in the undo tree, oldest first.
+    pub(crate) fn undo_states(&self) -> Vec<UndoState> {
+        self.history.states()
+    }
+
+    /// Discard any pending edits and move through the undo history using `walk`,
+    /// updating the dirty flag if the buffer changed.
+    fn walk_history<F: FnOnce(&mut History, &mut Buffer) -> bool>(&mut self, walk: F) -> bool {
+        self.buffer.discard_edits();
+        let moved = walk(&mut self.history, &mut self.buffer);
+
+        if moved {
+            self.dirty = !self.buffer.content_eq(&self.lsave);
         }
 
-        redone
+        moved
     }
 }
 
-fn print_styled(style: ColorStyle) -> impl FnMut(&Printer<'_, '_>, Vec2, char) {
-    let mut buf = vec![0; 4];
-    move |p, pos, c| {
-        p.with_color(style, |p| p.print(pos, c.encode_utf8(&mut buf)));
+fn print_styled(style: ColorStyle) -> impl FnMut(&Printer<'_, '_>, Vec2, Glyph) {
+    let mut buf = String::new();
+    move |p, pos, g| {
+        buf.clear();
+        buf.extend(g.into_chars());
+        p.with_color(style, |p| p.print(pos, &buf));
     }
 }
@@ -1,46 +1,167 @@
 use cursive::{Rect, Vec2,};
 use std::{
-    cmp::{max, min},
+    cmp::max,
+    collections::HashMap,
     io::{self, BufRead, BufReader, Read},
-    iter, mem,
+    mem,
 };
 
 use crate::constants::{
     D, D2,
     SP,
 };
-use crate::editor::cell::{Cell, Char};
-use crate::utils::ordfloat::OrdFloat;
+use crate::editor::{
+    cell::{Cell, Char, Glyph},
+    grid::Grid,
+    history::{CellDelta, Delta},
+};
+use crate::utils::{graphemes, ordfloat::OrdFloat};
 use crate::config::Symbols;
 
-#[derive(Clone, Default, PartialEq, Eq)]
+/// The line ending style of a diagram.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) enum LineEnding {
+    #[default]
+    Lf,
+    CrLf,
+}
+
+impl LineEnding {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+        }
+    }
+}
+
+#[derive(Clone, Default)]
 pub(crate) struct Buffer {
-    pub chars: Vec<Vec<char>>,
-    pub edits: Vec<Cell>,
+    grid: Grid,
+    line_ending: LineEnding,
+    /// Pending edits, indexed by position. Each position keeps every glyph written to
+    /// it in order, the last of which is the one that shows.
+    edits: HashMap<Vec2, Vec<Glyph>>,
     pub cursor: Option<Vec2>,
+    journal: Option<Delta>,
 }
 
 impl Buffer {
-    pub(crate) fn read_from<R: Read>(r: R) -> io::Result<Self> {
+    /// Read a diagram from `r`, expanding tabs to stops every `tab_width` columns.
+    ///
+    /// The line ending used by most lines is kept, and written back out by `iter`.
+    pub(crate) fn read_from<R: Read>(r: R, tab_width: usize) -> io::Result<Self> {
+        let mut grid = Grid::default();
+        let (mut lf, mut crlf) = (0, 0);
+
+        let mut r = BufReader::new(r);
+        let mut line = String::new();
+        while r.read_line(&mut line)? > 0 {
+            if line.ends_with("\r\n") {
+                line.truncate(line.len() - 2);
+                crlf += 1;
+            } else if line.ends_with('\n') {
+                line.pop();
+                lf += 1;
+            }
+
+            grid.push_line(expand_tabs(&line, tab_width));
+            line.clear();
+        }
+
+        let line_ending = if crlf > lf { LineEnding::CrLf } else { LineEnding::Lf };
+
         Ok(Self {
-            chars: BufReader::new(r)
-                .lines()
-                .map(|lr| lr.map(|s| s.chars().collect()))
-                .collect::<io::Result<_>>()?,
-            edits: vec![],
+            grid,
+            line_ending,
+            edits: HashMap::new(),
             cursor: None,
+            journal: None,
         })
     }
 
-    /// Returns a copy of this buffer without any pending edits.
-    pub(crate) fn snapshot(&self) -> Self {
-        Self {
-            chars: self.chars.clone(),
-            edits: vec![],
-            cursor: None,
+    /// Returns `true` if this buffer has the same committed content as `other`.
+    ///
+    /// Does not consider pending edits or the cursor.
+    pub(crate) fn content_eq(&self, other: &Self) -> bool {
+        self.grid == other.grid
+    }
+
+    /// Start recording every modification to the primary buffer, until `end_delta` is
+    /// called.
+    pub(crate) fn begin_delta(&mut self) {
+        self.journal = Some(Delta {
+            cells: vec![],
+            lines: (self.grid.lines(), self.grid.lines()),
+        });
+    }
+
+    /// Stop recording modifications, returning everything recorded since `begin_delta`.
+    pub(crate) fn end_delta(&mut self) -> Delta {
+        let mut delta = self.journal.take().unwrap_or_default();
+        delta.lines.1 = self.grid.lines();
+        delta
+    }
+
+    /// Apply `delta` to the primary buffer, or revert it if `revert` is set.
+    pub(crate) fn apply_delta(&mut self, delta: &Delta, revert: bool) {
+        if revert {
+            for cd in delta.cells.iter().rev() {
+                self.put(cd.pos, cd.old.clone());
+            }
+            self.grid.set_lines(delta.lines.0);
+        } else {
+            for cd in &delta.cells {
+                self.put(cd.pos, cd.new.clone());
+            }
+            self.grid.set_lines(delta.lines.1);
+        }
+    }
+
+    /// Set the committed cell at `pos`, allocating as necessary. `None` truncates the
+    /// line at `pos`.
+    fn put(&mut self, pos: Vec2, g: Option<Glyph>) {
+        match g {
+            Some(g) => self.grid.set(pos, g),
+            None => self.grid.truncate_line(pos.y, pos.x),
+        }
+    }
+
+    /// Record that the committed cell at `pos` changed from `old` to `new`, if a delta
+    /// is being recorded.
+    fn record(&mut self, pos: Vec2, old: Option<Glyph>, new: Option<Glyph>) {
+        if let Some(delta) = self.journal.as_mut() {
+            if old != new {
+                delta.cells.push(CellDelta { pos, old, new });
+            }
+        }
+    }
+
+    /// Record the difference between `before` and the current committed content, if a
+    /// delta is being recorded.
+    fn record_rewrite(&mut self, before: &Grid) {
+        let delta = match self.journal.as_mut() {
+            Some(d) => d,
+            None => return,
+        };
+
+        for y in 0..max(before.lines(), self.grid.lines()) {
+            for x in 0..max(before.line_len(y), self.grid.line_len(y)) {
+                let pos = Vec2::new(x, y);
+                let old = before.get(pos);
+                let new = self.grid.get(pos);
+                if old != new {
+                    delta.cells.push(CellDelta { pos, old, new });
+                }
+            }
         }
     }
 
+    /// Returns the line ending written out by `iter`.
+    pub(crate) fn line_ending(&self) -> LineEnding {
+        self.line_ending
+    }
+
     /// Set the cursor position to `pos`.
     pub(crate) fn set_cursor(&mut self, pos: Vec2) {
         self.cursor = Some(pos);
@@ -54,7 +175,8 @@ impl Buffer {
 
     /// Clears all content in the buffer.
     pub(crate) fn clear(&mut self) {
-        self.chars.clear();
+        self.grid.clear();
+        self.line_ending = LineEnding::default();
         self.edits.clear();
         self.cursor = None;
     }
@@ -63,15 +185,15 @@ impl Buffer {
     pub(crate) fn bounds(&self) -> Vec2 {
         let mut bounds = Vec2::new(0, 0);
 
-        for (y, line) in self.chars.iter().enumerate() {
-            if let Some(x) = line.iter().rposition(|&c| !c.is_whitespace()) {
+        for (y, _) in self.grid.rows() {
+            if let Some(x) = self.last_visible(y) {
                 bounds.x = max(bounds.x, x + 1);
                 bounds.y = max(bounds.y, y + 1);
             }
         }
 
-        for Cell { pos, c } in &self.edits {
-            if !c.is_whitespace() {
+        for Cell { pos, glyph } in self.pending() {
+            if !glyph.is_whitespace() {
                 bounds.x = max(bounds.x, pos.x + 1);
                 bounds.y = max(bounds.y, pos.y + 1);
             }
@@ -85,6 +207,22 @@ impl Buffer {
         bounds
     }
 
+    /// Returns the column of the first non-whitespace character on line `y`, if any.
+    ///
+    /// Does not consider any pending edits.
+    pub(crate) fn first_visible(&self, y: usize) -> Option<usize> {
+        self.grid.row(y).position(|c| !c.is_whitespace())
+    }
+
+    /// Returns the column of the last non-whitespace character on line `y`, if any.
+    ///
+    /// Does not consider any pending edits.
+    pub(crate) fn last_visible(&self, y: usize) -> Option<usize> {
+        (0..self.grid.line_len(y))
+            .rev()
+            .find(|&x| self.grid.get(Vec2::new(x, y)).is_some_and(|c| !c.is_whitespace()))
+    }
+
     /// Returns an iterator over all characters within the viewport formed by `offset`
     /// and `size`.
     pub(crate) fn iter_within(
@@ -96,124 +234,109 @@ impl Buffer {
         let area = Rect::from_corners(offset, offset + size);
         
         let cursor_char = self.cursor
-            .map(|pos| self.get_char_at(pos))
-            .unwrap_or(symbols.curs);
-
-        self.chars
-            .iter()
-            .enumerate()
-            .skip(offset.y)
-            .take(size.y)
-            .flat_map(move |(y, xs)| {
-                xs.iter()
-                    .copied()
-                    .enumerate()
-                    .skip(offset.x)
+            .map(|pos| self.glyph_at(pos))
+            .unwrap_or(Glyph::Char(symbols.curs));
+
+        self.grid
+            .rows_in(offset.y..offset.y + size.y)
+            .flat_map(move |(y, _)| {
+                self.grid
+                    .row_from(y, offset.x)
                     .take(size.x)
-                    .map(move |(x, c)| (Vec2::new(x, y), c))
-                    .map(|(pos, c)| Cell { pos, c })
+                    .enumerate()
+                    .map(move |(i, c)| (Vec2::new(offset.x + i, y), c))
+                    .map(|(pos, glyph)| Cell { pos, glyph })
                     .map(Char::Clean)
             })
             .chain(
-                self.edits
-                    .iter()
-                    .copied()
+                self.pending()
                     .filter(move |Cell { pos, .. }| area.contains(*pos))
                     .map(Char::Dirty),
             )
             .chain(
                 self.cursor
-                    .map(|pos| Cell { pos, c: cursor_char })
+                    .map(|pos| Cell { pos, glyph: cursor_char })
                     .map(Char::Cursor),
             )
     }
 
+    /// Returns the `char` that drawing tools see at `pos` (see [Glyph::base]).
     pub(crate) fn get_char_at(&self, pos: Vec2) -> char {
-        self.edits.iter().rev()
-            .find(|cell| cell.pos == pos)
-            .map(|cell| cell.c)
-            .or_else(|| self.getv(pos))
+        self.edits
+            .get(&pos)
+            .and_then(|gs| gs.last().map(Glyph::base))
+            .or_else(|| self.getv(pos).map(|g| g.base()))
             .unwrap_or(SP)
     }
 
-    /// Returns an iterator over all characters in the buffer, injecting newlines
+    /// Returns the glyph shown at `pos`.
+    pub(crate) fn glyph_at(&self, pos: Vec2) -> Glyph {
+        self.edits
+            .get(&pos)
+            .and_then(|gs| gs.last().cloned())
+            .or_else(|| self.getv(pos))
+            .unwrap_or(Glyph::Char(SP))
+    }
+
+    /// Returns an iterator over all characters in the buffer, injecting line endings
     /// where appropriate, with `prefix` before each line.
     pub(crate) fn iter<'a>(&'a self, prefix: &'a str) -> impl Iterator<Item = char> + 'a {
-        self.chars.iter().flat_map(move |line| {
+        (0..self.grid.lines()).flat_map(move |y| {
             prefix
                 .chars()
-                .chain(line.iter().copied())
-                .chain(iter::once('\n'))
+                .chain(self.grid.row(y).flat_map(Glyph::into_chars))
+                .chain(self.line_ending.as_str().chars())
         })
     }
 
     /// Strip margin whitespace from the buffer.
     pub(crate) fn strip_margin_whitespace(&mut self) {
-        let is_only_ws = |v: &[char]| v.iter().all(|c| c.is_whitespace());
-
-        // upper margin
-        for _ in 0..self
-            .chars
-            .iter()
-            .take_while(|line| is_only_ws(line))
-            .count()
-        {
-            self.chars.remove(0);
-        }
-
-        // lower margin
-        for _ in 0..self
-            .chars
-            .iter()
-            .rev()
-            .take_while(|line| is_only_ws(line))
-            .count()
-        {
-            self.chars.pop();
-        }
-
-        // left margin
-        if let Some(min_ws) = self
-            .chars
-            .iter()
-            .filter(|line| !is_only_ws(line))
-            .map(|line| line.iter().position(|c| !c.is_whitespace()))
-            .min()
-            .flatten()
-        {
-            for line in self.chars.iter_mut() {
-                if line.is_empty() {
-                    continue;
-                }
-                let idx = min(line.len() - 1, min_ws);
-                let new = line.split_off(idx);
-                let _ = mem::replace(line, new);
+        let visible: Vec<_> = self
+            .grid
+            .rows()
+            .filter_map(|(y, _)| self.first_visible(y).map(|x| (y, x)))
+            .collect();
+
+        let (top, bottom) = match (visible.first(), visible.last()) {
+            (Some(&(top, _)), Some(&(bottom, _))) => (top, bottom),
+            _ => (0, 0),
+        };
+        let left = visible.iter().map(|&(_, x)| x).min().unwrap_or(0);
+
+        // shifting the margins moves every cell, so rebuild the grid and diff the whole
+        // buffer afterwards rather than recording each step
+        let mut grid = Grid::default();
+        if !visible.is_empty() {
+            for y in top..=bottom {
+                let end = self.last_visible(y).map_or(0, |x| x + 1);
+                grid.push_line(self.grid.row(y).take(end).skip(left));
             }
         }
 
-        // right margin
-        self.strip_trailing_whitespace();
+        let before = mem::replace(&mut self.grid, grid);
+        self.record_rewrite(&before);
     }
 
     /// Strip trailing whitespace from the buffer.
     pub(crate) fn strip_trailing_whitespace(&mut self) {
-        for line in self.chars.iter_mut() {
-            let idx = line
-                .iter()
-                .enumerate()
-                .rfind(|p| !p.1.is_whitespace())
-                .map(|p| p.0 + 1)
-                .unwrap_or(0);
-
-            line.truncate(idx);
+        let rows: Vec<_> = self.grid.rows().collect();
+
+        for (y, len) in rows {
+            let idx = self.last_visible(y).map_or(0, |x| x + 1);
+
+            for x in idx..len {
+                let pos = Vec2::new(x, y);
+                self.record(pos, self.grid.get(pos), None);
+            }
+            self.grid.truncate_line(y, idx);
         }
     }
 
     /// Get the cell at `pos`, if it exists.
     ///
     /// Does not consider any pending edits.
-    pub(crate) fn getv(&self, pos: Vec2) -> Option<char> {
-        self.chars.get(pos.y).and_then(|v| v.get(pos.x)).copied()
+    pub(crate) fn getv(&self, pos: Vec2) -> Option<Glyph> {
+        self.grid.get(pos)
     }
 
     /// Returns `true` iff the cell at `pos` exists and contains a non-whitespace
@@ -221,35 +344,44 @@ impl Buffer {
     ///
     /// Does not consider any pending edits.
     pub(crate) fn visible(&self, pos: Vec2) -> bool {
-        !self.get_char_at(pos).is_whitespace()
+        !self.glyph_at(pos).is_whitespace()
     }
 
     /// Set the cell at `pos` to `c`.
+    ///
+    /// Unless `force` is set, `c` is dropped if the cell already holds a character of
+    /// higher precedence, or `c` itself. The committed value and each pending edit are
+    /// considered in order, and a pending blank clears everything before it, so that a
+    /// cell erased earlier in the same edit can be drawn over again.
     pub(crate) fn setv(&mut self, force: bool, pos: Vec2, c: char, symbols: &Symbols) {
         if force {
-            self.edits.push(Cell { pos, c });
+            self.set_glyph(pos, Glyph::Char(c));
             return;
         }
 
         let max_prec = precedence(c, symbols);
-        let overrides = |_c| _c == c || precedence(_c, symbols) > max_prec;
+        let overrides = |old: &Glyph| match *old {
+            Glyph::Char(old) => old == c || precedence(old, symbols) > max_prec,
+            _ => false,
+        };
+
+        let pending = self.edits.get(&pos).into_iter().flatten().cloned();
 
         let mut overridden = false;
-        if self.chars.len() > pos.y && self.chars[pos.y].len() > pos.x {
-            overridden |= overrides(self.chars[pos.y][pos.x]);
+        for old in self.grid.get(pos).into_iter().chain(pending) {
+            overridden = !old.is_whitespace() && (overridden || overrides(&old));
         }
 
-        overridden |= self
-            .edits
-            .iter()
-            .filter(|cell| cell.pos == pos)
-            .any(|cell| overrides(cell.c));
-
         if !overridden {
-            self.edits.push(Cell { pos, c });
+            self.edits.entry(pos).or_default().push(Glyph::Char(c));
         }
     }
 
+    /// Set the cell at `pos` to `g`, whatever is already there.
+    pub(crate) fn set_glyph(&mut self, pos: Vec2, g: Glyph) {
+        self.edits.entry(pos).or_default().push(g);
+    }
+
     /// Set the cell at `(x, y)` to `c`.
     pub(crate) fn set(&mut self, force: bool, x: usize, y: usize, c: char, symbols: &Symbols) {
         self.setv(force, Vec2::new(x, y), c, symbols)
@@ -257,22 +389,58 @@ impl Buffer {
 
     /// Flush any pending edits to the primary buffer, allocating as necessary.
     pub(crate) fn flush_edits(&mut self) {
-        for Cell {
-            pos: Vec2 { x, y },
-            c,
-            ..
-        } in self.edits.drain(..)
-        {
-            if self.chars.len() <= y {
-                self.chars.resize_with(y + 1, Vec::default);
+        let mut edits: Vec<_> = self.pending().collect();
+        edits.sort_unstable_by_key(|cell| (cell.pos.y, cell.pos.x));
+        self.edits.clear();
+
+        let flushed: Vec<_> = edits.iter().map(Cell::pos).collect();
+        for Cell { pos, glyph } in edits {
+            // extending a line implicitly pads it with blanks. recording where the line
+            // used to end is enough for undo to restore its old length
+            let len = self.grid.line_len(pos.y);
+            if len < pos.x {
+                self.record(Vec2::new(len, pos.y), None, Some(Glyph::Char(SP)));
             }
-            if self.chars[y].len() <= x {
-                self.chars[y].resize(x + 1, SP);
+
+            let old = self.getv(pos);
+            self.put(pos, Some(glyph.clone()));
+            self.record(pos, old, Some(glyph));
+        }
+
+        for pos in flushed {
+            self.fix_wide(pos);
+        }
+    }
+
+    /// Blank out any half of a double width cell at or next to `pos` that lost its
+    /// other half.
+    fn fix_wide(&mut self, pos: Vec2) {
+        let is_wide = |g: &Option<Glyph>| g.as_ref().is_some_and(|g| graphemes::width(g) == 2);
+
+        for x in pos.x.saturating_sub(1)..=pos.x + 1 {
+            let at = |x| self.getv(Vec2::new(x, pos.y));
+            let old = at(x);
+            let orphan = match &old {
+                Some(Glyph::Wide) => x == 0 || !is_wide(&at(x - 1)),
+                g if is_wide(g) => at(x + 1) != Some(Glyph::Wide),
+                _ => false,
+            };
+
+            if orphan {
+                let cell = Vec2::new(x, pos.y);
+                self.record(cell, old, Some(Glyph::Char(SP)));
+                self.put(cell, Some(Glyph::Char(SP)));
             }
-            self.chars[y][x] = c;
         }
     }
 
+    /// Returns an iterator over the pending edit that shows at each position.
+    fn pending(&self) -> impl Iterator<Item = Cell> + '_ {
+        self.edits
+            .iter()
+            .filter_map(|(&pos, gs)| gs.last().map(|g| Cell { pos, glyph: g.clone() }))
+    }
+
     /// Discard any pending edits.
     pub(crate) fn discard_edits(&mut self) {
         self.edits.clear();
@@ -319,6 +487,23 @@ impl Buffer {
     }
 }
 
+/// Returns the buffer cells for `line`, with each tab expanded to blanks up to the next
+/// multiple of `tab_width` columns.
+fn expand_tabs(line: &str, tab_width: usize) -> Vec<Glyph> {
+    let tab_width = max(tab_width, 1);
+    let mut cells = vec![];
+
+    for (i, part) in line.split('\t').enumerate() {
+        if i > 0 {
+            let stop = (cells.len() / tab_width + 1) * tab_width;
+            cells.resize(stop, Glyph::Char(SP));
+        }
+        cells.extend(graphemes::cells(part));
+    }
+
+    cells
+}
+
 /// Returns the overlap precedence for `c`.
 /// Higher values mean the character is "stronger" and less likely to be overwritten
 /// by other characters during non-forced updates.
@@ -331,3 +516,64 @@ fn precedence(c: char, symbols: &Symbols) -> usize {
     else { 0 }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str = "┌──┐ 中\n│👨\u{200d}👩\u{200d}👧│\u{f0000}\u{10fffd}\n└──┘ e\u{301}\n";
+
+    #[test]
+    fn clusters_survive_load_and_save() {
+        let buf = Buffer::read_from(TEXT.as_bytes(), 8).unwrap();
+
+        assert_eq!(buf.iter("").collect::<String>(), TEXT);
+        assert_eq!(buf.glyph_at(Vec2::new(5, 0)), Glyph::Char('中'));
+        assert_eq!(buf.glyph_at(Vec2::new(6, 0)), Glyph::Wide);
+        assert_eq!(buf.glyph_at(Vec2::new(1, 1)), Glyph::Cluster("👨\u{200d}👩\u{200d}👧".into()));
+        assert_eq!(buf.glyph_at(Vec2::new(4, 1)), Glyph::Char('\u{f0000}'));
+        assert_eq!(buf.get_char_at(Vec2::new(5, 2)), 'e');
+    }
+
+    #[test]
+    fn overwriting_half_a_wide_glyph_blanks_the_other() {
+        let mut buf = Buffer::read_from(TEXT.as_bytes(), 8).unwrap();
+
+        buf.set_glyph(Vec2::new(6, 0), Glyph::Char('x'));
+        buf.set_glyph(Vec2::new(1, 1), Glyph::Char('y'));
+        buf.flush_edits();
+
+        let text: String = buf.iter("").collect();
+        assert_eq!(text.lines().take(2).collect::<Vec<_>>(), ["┌──┐  x", "│y │\u{f0000}\u{10fffd}"]);
+    }
+
+    #[test]
+    fn tabs_expand_to_stops() {
+        let buf = Buffer::read_from("a\tb\n中\tc\n".as_bytes(), 4).unwrap();
+        assert_eq!(buf.iter("").collect::<String>(), "a   b\n中  c\n");
+    }
+
+    #[test]
+    fn setv_keeps_higher_precedence() {
+        let symbols = Symbols::default();
+        let mut buf = Buffer::read_from("+-|\n".as_bytes(), 8).unwrap();
+
+        buf.setv(false, Vec2::new(0, 0), '-', &symbols);
+        buf.setv(false, Vec2::new(1, 0), '+', &symbols);
+        buf.setv(false, Vec2::new(2, 0), '-', &symbols);
+        buf.setv(false, Vec2::new(2, 0), '/', &symbols);
+        buf.flush_edits();
+        assert_eq!(buf.iter("").collect::<String>(), "++-\n");
+    }
+
+    #[test]
+    fn setv_draws_over_pending_blanks() {
+        let symbols = Symbols::default();
+        let mut buf = Buffer::read_from("+\n".as_bytes(), 8).unwrap();
+
+        buf.setv(true, Vec2::new(0, 0), SP, &symbols);
+        buf.setv(false, Vec2::new(0, 0), '|', &symbols);
+        buf.setv(false, Vec2::new(0, 0), '/', &symbols);
+        buf.flush_edits();
+        assert_eq!(buf.iter("").collect::<String>(), "|\n");
+    }
+}
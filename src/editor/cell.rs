@@ -1,9 +1,67 @@
 use cursive::{Vec2, XY};
+use std::fmt;
 
-#[derive(Copy, Clone, PartialEq, Eq)]
+use crate::constants::SP;
+
+/// The content of a single buffer cell.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum Glyph {
+    /// A grapheme cluster made of a single `char`.
+    Char(char),
+    /// A grapheme cluster made of several `char`s.
+    Cluster(Box<str>),
+    /// The second column of the double width glyph to its left.
+    Wide,
+}
+
+impl Glyph {
+    /// Returns the `char` that drawing tools see in this cell: the first of a cluster,
+    /// or a blank for the second column of a double width glyph.
+    pub(crate) fn base(&self) -> char {
+        match self {
+            Glyph::Char(c) => *c,
+            Glyph::Cluster(s) => s.chars().next().unwrap_or(SP),
+            Glyph::Wide => SP,
+        }
+    }
+
+    /// Returns `true` if this is a single whitespace `char`.
+    pub(crate) fn is_whitespace(&self) -> bool {
+        matches!(self, Glyph::Char(c) if c.is_whitespace())
+    }
+
+    /// Returns the characters to write out for this glyph.
+    pub(crate) fn into_chars(self) -> impl Iterator<Item = char> {
+        let (c, cluster) = match self {
+            Glyph::Char(c) => (Some(c), None),
+            Glyph::Cluster(s) => (None, Some(s.chars().collect::<Vec<_>>())),
+            Glyph::Wide => (None, None),
+        };
+
+        c.into_iter().chain(cluster.into_iter().flatten())
+    }
+}
+
+impl From<char> for Glyph {
+    fn from(c: char) -> Self {
+        Glyph::Char(c)
+    }
+}
+
+impl fmt::Display for Glyph {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Glyph::Char(c) => write!(f, "{}", c),
+            Glyph::Cluster(s) => write!(f, "{}", s),
+            Glyph::Wide => Ok(()),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq)]
 pub(crate) struct Cell {
     pub pos: Vec2,
-    pub c: char,
+    pub glyph: Glyph,
 }
 
 impl Cell {
@@ -11,12 +69,17 @@ impl Cell {
         self.pos
     }
 
+    /// Returns the `char` that drawing tools see in this cell (see [Glyph::base]).
     pub(crate) fn c(&self) -> char {
-        self.c
+        self.glyph.base()
+    }
+
+    pub(crate) fn glyph(&self) -> &Glyph {
+        &self.glyph
     }
 
     pub(crate) fn is_whitespace(&self) -> bool {
-        self.c.is_whitespace()
+        self.glyph.is_whitespace()
     }
 
     pub(crate) fn translate(mut self, by: XY<isize>) -> Self {
@@ -25,7 +88,7 @@ impl Cell {
     }
 }
 
-#[derive(Copy, Clone, PartialEq, Eq)]
+#[derive(Clone, PartialEq, Eq)]
 pub(crate) enum Char {
     Clean(Cell),
     Dirty(Cell),
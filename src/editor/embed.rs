@@ -0,0 +1,316 @@
+use std::path::{Path, PathBuf};
+
+/// Comment and quote markers that a diagram embedded in source code or Markdown may be
+/// prefixed with, longest first.
+const COMMENT_MARKERS: [&str; 5] = ["///", "//!", "//", "#", ">"];
+
+/// A diagram embedded in a region of a larger host file, such as a fenced block in a
+/// Markdown file or a comment block in source code.
+#[derive(Clone, Debug)]
+pub(crate) struct Embed {
+    /// Line number of the first line of the region, starting at 1.
+    pub line: usize,
+    /// Host file contents before the region.
+    head: String,
+    /// Host file contents after the region.
+    tail: String,
+    /// Prefix stripped from every line of the region, such as `// `.
+    prefix: String,
+}
+
+impl Embed {
+    /// Find the diagram region containing line `line` (starting at 1) of `host`. If
+    /// `host` is Markdown, lines starting with `#` are headings rather than comments.
+    ///
+    /// Returns the region along with its contents, with any comment prefix or fence
+    /// indentation stripped from each line.
+    pub(crate) fn find(host: &str, line: usize, markdown: bool) -> Option<(Self, String)> {
+        let lines: Vec<_> = host.split_inclusive('\n').collect();
+        let l = line.checked_sub(1).filter(|&l| l < lines.len())?;
+
+        let (start, end, prefix) = fenced_blocks(&lines)
+            .into_iter()
+            .find(|&(open, close)| open <= l && close.is_none_or(|c| l <= c))
+            .map(|(open, close)| {
+                let indent = indent_of(lines[open]).to_owned();
+                (open + 1, close.unwrap_or(lines.len()), indent)
+            })
+            .or_else(|| comment_block(&lines, l, markdown))?;
+
+        let region = strip_lines(&lines[start..end], &prefix);
+
+        let embed = Self {
+            line: start + 1,
+            head: lines[..start].concat(),
+            tail: lines[end..].concat(),
+            prefix,
+        };
+
+        Some((embed, region))
+    }
+
+    /// Returns the host file contents with the region replaced by `rendered`, putting
+    /// the prefix back on every line.
+    pub(crate) fn splice(&self, rendered: &str) -> String {
+        let mut out = self.head.clone();
+
+        for line in rendered.split_inclusive('\n') {
+            let (content, ending) = split_ending(line);
+            if content.is_empty() {
+                out.push_str(self.prefix.trim_end());
+            } else {
+                out.push_str(&self.prefix);
+                out.push_str(content);
+            }
+            out.push_str(ending);
+        }
+
+        out.push_str(&self.tail);
+        out
+    }
+}
+
+/// Returns `text` without the comment or quote prefix shared by all of its non-empty
+/// lines, such as `// ` or `> `. If there is no such prefix, `text` is returned as is.
+pub(crate) fn strip_comment_prefix(text: &str) -> String {
+    let lines: Vec<_> = text.split_inclusive('\n').collect();
+    let mut markers = lines
+        .iter()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| comment_marker(l));
+
+    let marker = match markers.next() {
+        Some(Some(m)) if markers.all(|n| n.as_ref() == Some(&m)) => m,
+        _ => return text.to_owned(),
+    };
+
+    let non_empty: Vec<_> = lines.iter().copied().filter(|l| !l.trim().is_empty()).collect();
+    strip_lines(&lines, &prefix_for(&non_empty, marker))
+}
+
+/// Returns the line number (starting at 1) of the first line inside every fenced block
+/// in `host`, along with a preview of its contents.
+pub(crate) fn fenced_block_list(host: &str) -> Vec<(usize, String)> {
+    let lines: Vec<_> = host.split_inclusive('\n').collect();
+
+    fenced_blocks(&lines)
+        .into_iter()
+        .map(|(open, close)| {
+            let inside = &lines[open + 1..close.unwrap_or(lines.len())];
+            let preview = inside
+                .iter()
+                .map(|l| l.trim())
+                .find(|l| !l.is_empty())
+                .unwrap_or("(empty)")
+                .chars()
+                .take(40)
+                .collect();
+            (open + 2, preview)
+        })
+        .collect()
+}
+
+/// Returns `true` if `path` names a Markdown file.
+pub(crate) fn is_markdown(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ["md", "markdown", "mdx"].contains(&ext.to_lowercase().as_str()))
+}
+
+/// Splits a `path:LINE` location into the path and line number. If `raw` does not end
+/// with a line number, or names a file that exists as is, the line number is `None`.
+pub(crate) fn split_location(raw: &Path) -> (PathBuf, Option<usize>) {
+    let s = raw.to_string_lossy();
+
+    match s.rsplit_once(':') {
+        Some((path, line)) if !raw.exists() && !path.is_empty() => match line.parse() {
+            Ok(line) => (PathBuf::from(path), Some(line)),
+            Err(_) => (raw.into(), None),
+        },
+        _ => (raw.into(), None),
+    }
+}
+
+/// Returns the indices of the opening and closing fence line of every fenced block in
+/// `lines`. Blocks that are never closed run to the end of the file.
+fn fenced_blocks(lines: &[&str]) -> Vec<(usize, Option<usize>)> {
+    let mut blocks = vec![];
+    let mut open: Option<(usize, char, usize)> = None;
+
+    for (i, line) in lines.iter().enumerate() {
+        let trimmed = line.trim();
+        let fence = trimmed.chars().next().filter(|&c| c == '`' || c == '~');
+        let len = fence.map_or(0, |f| trimmed.chars().take_while(|&c| c == f).count());
+
+        match (open, fence) {
+            (None, Some(f)) if len >= 3 => open = Some((i, f, len)),
+            (Some((start, f, n)), Some(c)) if c == f && len >= n && len == trimmed.len() => {
+                blocks.push((start, Some(i)));
+                open = None;
+            }
+            _ => {}
+        }
+    }
+
+    if let Some((start, _, _)) = open {
+        blocks.push((start, None));
+    }
+
+    blocks
+}
+
+/// Returns the start and end index of the run of comment lines around line `l`, along
+/// with their shared prefix. `#` is not a comment marker if `markdown` is set.
+fn comment_block(lines: &[&str], l: usize, markdown: bool) -> Option<(usize, usize, String)> {
+    let marker = comment_marker(lines[l]).filter(|m| !(markdown && m.ends_with('#')))?;
+    let same = |i: &usize| comment_marker(lines[*i]).as_deref() == Some(marker.as_str());
+
+    let start = (0..l).rev().take_while(same).last().unwrap_or(l);
+    let end = (l + 1..lines.len()).take_while(same).last().unwrap_or(l) + 1;
+
+    Some((start, end, prefix_for(&lines[start..end], marker)))
+}
+
+/// Returns the prefix to strip from `lines`, which all start with `marker`. The space
+/// after the marker is only included if every line that goes on past it has one.
+fn prefix_for(lines: &[&str], marker: String) -> String {
+    let spaced = lines.iter().all(|line| {
+        let rest = &split_ending(line).0[marker.len()..];
+        rest.is_empty() || rest.starts_with(' ')
+    });
+
+    if spaced { format!("{} ", marker) } else { marker }
+}
+
+/// Returns `lines` joined together, with `prefix` (or its trimmed form, on blank lines)
+/// stripped from the start of each one.
+fn strip_lines(lines: &[&str], prefix: &str) -> String {
+    let blank = prefix.trim_end();
+
+    lines
+        .iter()
+        .map(|line| {
+            let (content, ending) = split_ending(line);
+            let content = content
+                .strip_prefix(prefix)
+                .or_else(|| content.strip_prefix(blank))
+                .unwrap_or(content.trim_start());
+            format!("{}{}", content, ending)
+        })
+        .collect()
+}
+
+/// Returns the indentation and comment marker that `line` starts with, if any. A `#!`
+/// shebang line is not a comment.
+fn comment_marker(line: &str) -> Option<String> {
+    let indent = indent_of(line);
+    let rest = &line[indent.len()..];
+    if rest.starts_with("#!") {
+        return None;
+    }
+
+    COMMENT_MARKERS
+        .iter()
+        .find(|m| rest.starts_with(*m))
+        .map(|m| format!("{}{}", indent, m))
+}
+
+fn indent_of(line: &str) -> &str {
+    &line[..line.len() - line.trim_start_matches([' ', '\t']).len()]
+}
+
+/// Splits `line` into its contents and line ending.
+fn split_ending(line: &str) -> (&str, &str) {
+    let content = line.trim_end_matches(['\r', '\n']);
+    line.split_at(content.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MARKDOWN: &str = "# Title\n\n  ```\n  +--+\n\n  +--+\n  ```\ntext\n";
+
+    #[test]
+    fn fenced_block() {
+        let (embed, region) = Embed::find(MARKDOWN, 5, true).unwrap();
+        assert_eq!(embed.line, 4);
+        assert_eq!(region, "+--+\n\n+--+\n");
+
+        assert_eq!(embed.splice(&region), MARKDOWN);
+        assert_eq!(
+            embed.splice("+-+\n| |\n+-+\n"),
+            "# Title\n\n  ```\n  +-+\n  | |\n  +-+\n  ```\ntext\n",
+        );
+    }
+
+    #[test]
+    fn comment_block() {
+        let host = "fn main() {}\n\n    // +--+\n    //\n    // +--+\nfn f() {}\n";
+        let (embed, region) = Embed::find(host, 3, false).unwrap();
+        assert_eq!(embed.line, 3);
+        assert_eq!(region, "+--+\n\n+--+\n");
+
+        assert_eq!(embed.splice(&region), host);
+        assert_eq!(
+            embed.splice("a\n\nb\nc\n"),
+            "fn main() {}\n\n    // a\n    //\n    // b\n    // c\nfn f() {}\n",
+        );
+    }
+
+    #[test]
+    fn crlf_line_endings_are_kept() {
+        let host = "# a\r\n# b\r\nx\r\n";
+        let (embed, region) = Embed::find(host, 2, false).unwrap();
+        assert_eq!(region, "a\r\nb\r\n");
+        assert_eq!(embed.splice(&region), host);
+    }
+
+    #[test]
+    fn markdown_headings_are_not_comments() {
+        assert!(Embed::find(MARKDOWN, 1, true).is_none());
+        assert!(Embed::find(MARKDOWN, 8, true).is_none());
+        assert!(Embed::find("> +--+\n> +--+\n", 1, true).is_some());
+    }
+
+    #[test]
+    fn shebangs_are_not_comments() {
+        let host = "#!/bin/sh\n# +--+\n# +--+\necho\n";
+        let (embed, region) = Embed::find(host, 2, false).unwrap();
+        assert_eq!(embed.line, 2);
+        assert_eq!(region, "+--+\n+--+\n");
+        assert_eq!(embed.splice("+-+\n"), "#!/bin/sh\n# +-+\necho\n");
+
+        assert!(Embed::find(host, 1, false).is_none());
+    }
+
+    #[test]
+    fn lines_out_of_range() {
+        assert!(Embed::find(MARKDOWN, 0, true).is_none());
+        assert!(Embed::find(MARKDOWN, 100, true).is_none());
+    }
+
+    #[test]
+    fn unclosed_fences_run_to_the_end() {
+        let host = "a\n~~~~\nb\n~~~\nc\n";
+        let (embed, region) = Embed::find(host, 5, true).unwrap();
+        assert_eq!(region, "b\n~~~\nc\n");
+        assert_eq!(embed.splice("d\n"), "a\n~~~~\nd\n");
+        assert_eq!(fenced_block_list(host), [(3, "b".to_string())]);
+    }
+
+    #[test]
+    fn shared_comment_prefix() {
+        assert_eq!(strip_comment_prefix("// a\n//\n// b\n"), "a\n\nb\n");
+        assert_eq!(strip_comment_prefix("//a\n// b\n"), "a\n b\n");
+        assert_eq!(strip_comment_prefix("// a\n# b\n"), "// a\n# b\n");
+    }
+
+    #[test]
+    fn locations() {
+        assert_eq!(split_location(Path::new("no/such/file.md:12")), (PathBuf::from("no/such/file.md"), Some(12)));
+        assert_eq!(split_location(Path::new("no/such/file.md")), (PathBuf::from("no/such/file.md"), None));
+        assert!(is_markdown(Path::new("README.MD")));
+        assert!(!is_markdown(Path::new("main.rs")));
+    }
+}
@@ -0,0 +1,282 @@
+use cursive::Vec2;
+use std::{
+    cmp::min,
+    collections::{BTreeMap, HashMap},
+    ops::Range,
+};
+
+use crate::constants::SP;
+use crate::editor::cell::Glyph;
+
+const TILE_W: usize = 32;
+const TILE_H: usize = 16;
+
+type Tile = Box<[char; TILE_W * TILE_H]>;
+
+/// Committed buffer content, stored as fixed size tiles that are only allocated once
+/// something is written inside them.
+///
+/// Every line keeps its own length, so trailing whitespace and empty lines survive
+/// exactly as they were loaded or drawn. Cells past the end of a line are always blank
+/// in the tiles, which lets a line grow without writing any padding.
+///
+/// Tiles hold a `char` per cell. The few cells holding any other [Glyph] keep it in a
+/// separate map, with a blank under it in the tile.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Grid {
+    lines: usize,
+    /// Lengths of all non-empty lines.
+    lens: BTreeMap<usize, usize>,
+    tiles: HashMap<(usize, usize), Tile>,
+    glyphs: HashMap<Vec2, Glyph>,
+}
+
+impl PartialEq for Grid {
+    fn eq(&self, other: &Self) -> bool {
+        self.lines == other.lines
+            && self.lens == other.lens
+            && self
+                .lens
+                .iter()
+                .all(|(&y, _)| self.row(y).eq(other.row(y)))
+    }
+}
+
+impl Eq for Grid {}
+
+impl Grid {
+    /// Returns the number of lines.
+    pub(crate) fn lines(&self) -> usize {
+        self.lines
+    }
+
+    /// Returns the length of line `y`.
+    pub(crate) fn line_len(&self, y: usize) -> usize {
+        self.lens.get(&y).copied().unwrap_or(0)
+    }
+
+    /// Returns the line number and length of every non-empty line, in order.
+    pub(crate) fn rows(&self) -> impl DoubleEndedIterator<Item = (usize, usize)> + '_ {
+        self.lens.iter().map(|(&y, &len)| (y, len))
+    }
+
+    /// Returns the line number and length of every non-empty line within `ys`, in order.
+    pub(crate) fn rows_in(&self, ys: Range<usize>) -> impl DoubleEndedIterator<Item = (usize, usize)> + '_ {
+        self.lens.range(ys).map(|(&y, &len)| (y, len))
+    }
+
+    /// Returns the cells on line `y`.
+    pub(crate) fn row(&self, y: usize) -> impl Iterator<Item = Glyph> + '_ {
+        self.row_from(y, 0)
+    }
+
+    /// Returns the cells on line `y`, starting at column `x`.
+    pub(crate) fn row_from(&self, y: usize, x: usize) -> impl Iterator<Item = Glyph> + '_ {
+        (x..self.line_len(y)).map(move |x| self.cell(x, y))
+    }
+
+    /// Returns the cell at `pos`, or `None` if it is past the end of its line.
+    pub(crate) fn get(&self, pos: Vec2) -> Option<Glyph> {
+        (pos.x < self.line_len(pos.y)).then(|| self.cell(pos.x, pos.y))
+    }
+
+    /// Set the cell at `pos` to `g`, extending the line and the grid as necessary.
+    pub(crate) fn set(&mut self, pos: Vec2, g: Glyph) {
+        let Vec2 { x, y } = pos;
+
+        self.lines = self.lines.max(y + 1);
+        let len = self.lens.entry(y).or_insert(0);
+        *len = (*len).max(x + 1);
+
+        let c = match g {
+            Glyph::Char(c) => {
+                self.glyphs.remove(&pos);
+                c
+            }
+            g => {
+                self.glyphs.insert(pos, g);
+                SP
+            }
+        };
+
+        if c == SP && !self.tiles.contains_key(&tile_of(x, y)) {
+            return;
+        }
+
+        let tile = self
+            .tiles
+            .entry(tile_of(x, y))
+            .or_insert_with(|| Box::new([SP; TILE_W * TILE_H]));
+
+        tile[index_of(x, y)] = c;
+    }
+
+    /// Append a line containing `cells`.
+    pub(crate) fn push_line<I: IntoIterator<Item = Glyph>>(&mut self, cells: I) {
+        let y = self.lines;
+        self.lines += 1;
+
+        for (x, g) in cells.into_iter().enumerate() {
+            self.set(Vec2::new(x, y), g);
+        }
+    }
+
+    /// Shorten line `y` to `len` cells. Does nothing if it is already shorter.
+    pub(crate) fn truncate_line(&mut self, y: usize, len: usize) {
+        let old = self.line_len(y);
+        if len >= old {
+            return;
+        }
+
+        self.blank(y, len, old);
+
+        if len == 0 {
+            self.lens.remove(&y);
+        } else {
+            self.lens.insert(y, len);
+        }
+    }
+
+    /// Resize the grid to exactly `lines` lines, dropping any content past the end.
+    pub(crate) fn set_lines(&mut self, lines: usize) {
+        let dropped: Vec<_> = self.lens.range(lines..).map(|(&y, &len)| (y, len)).collect();
+
+        for (y, len) in dropped {
+            self.blank(y, 0, len);
+            self.lens.remove(&y);
+        }
+
+        self.lines = lines;
+    }
+
+    /// Remove all content.
+    pub(crate) fn clear(&mut self) {
+        self.lines = 0;
+        self.lens.clear();
+        self.tiles.clear();
+        self.glyphs.clear();
+    }
+
+    fn cell(&self, x: usize, y: usize) -> Glyph {
+        if let Some(g) = self.glyphs.get(&Vec2::new(x, y)) {
+            return g.clone();
+        }
+
+        let c = self
+            .tiles
+            .get(&tile_of(x, y))
+            .map(|tile| tile[index_of(x, y)])
+            .unwrap_or(SP);
+
+        Glyph::Char(c)
+    }
+
+    /// Reset cells `from..to` on line `y` to blanks, freeing any tiles left empty.
+    fn blank(&mut self, y: usize, from: usize, to: usize) {
+        if !self.glyphs.is_empty() {
+            self.glyphs.retain(|pos, _| pos.y != y || !(from..to).contains(&pos.x));
+        }
+
+        let mut x = from;
+        while x < to {
+            let key = tile_of(x, y);
+            let end = min(to, (key.0 + 1) * TILE_W);
+
+            if let Some(tile) = self.tiles.get_mut(&key) {
+                for i in x..end {
+                    tile[index_of(i, y)] = SP;
+                }
+                if tile.iter().all(|&c| c == SP) {
+                    self.tiles.remove(&key);
+                }
+            }
+
+            x = end;
+        }
+    }
+}
+
+fn tile_of(x: usize, y: usize) -> (usize, usize) {
+    (x / TILE_W, y / TILE_H)
+}
+
+fn index_of(x: usize, y: usize) -> usize {
+    (y % TILE_H) * TILE_W + x % TILE_W
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(grid: &Grid, y: usize) -> String {
+        grid.row(y).flat_map(Glyph::into_chars).collect()
+    }
+
+    #[test]
+    fn lines_keep_their_length() {
+        let mut grid = Grid::default();
+        grid.push_line("ab  ".chars().map(Glyph::Char));
+        grid.push_line(std::iter::empty());
+        grid.set(Vec2::new(TILE_W + 2, 2), Glyph::Char('x'));
+
+        assert_eq!(grid.lines(), 3);
+        assert_eq!(line(&grid, 0), "ab  ");
+        assert_eq!(grid.line_len(1), 0);
+        assert_eq!(line(&grid, 2), format!("{}x", " ".repeat(TILE_W + 2)));
+        assert_eq!(grid.get(Vec2::new(4, 0)), None);
+        assert_eq!(grid.get(Vec2::new(1, 0)), Some(Glyph::Char('b')));
+    }
+
+    #[test]
+    fn shortened_lines_grow_back_blank() {
+        let mut grid = Grid::default();
+        grid.push_line("abcdef".chars().map(Glyph::Char));
+        grid.push_line("ghi".chars().map(Glyph::Char));
+
+        grid.truncate_line(0, 2);
+        grid.set(Vec2::new(5, 0), Glyph::Char('z'));
+        assert_eq!(line(&grid, 0), "ab   z");
+
+        grid.set_lines(1);
+        grid.set(Vec2::new(0, 1), Glyph::Char('j'));
+        assert_eq!(line(&grid, 1), "j");
+    }
+
+    #[test]
+    fn glyphs_are_dropped_with_their_line() {
+        let mut grid = Grid::default();
+        grid.push_line([Glyph::Char('a'), Glyph::Cluster("e\u{301}".into()), Glyph::Char('中'), Glyph::Wide]);
+        assert_eq!(line(&grid, 0), "ae\u{301}中");
+
+        grid.truncate_line(0, 1);
+        grid.set(Vec2::new(3, 0), Glyph::Char('b'));
+        assert_eq!(grid.row(0).collect::<Vec<_>>(), "a  b".chars().map(Glyph::Char).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn rows_in_range() {
+        let mut grid = Grid::default();
+        for y in [0, 3, 5, 6, TILE_H * 3] {
+            grid.set(Vec2::new(y, y), Glyph::Char('x'));
+        }
+
+        let rows: Vec<_> = grid.rows_in(3..6).collect();
+        assert_eq!(rows, [(3, 4), (5, 6)]);
+        assert_eq!(grid.rows_in(4..5).count(), 0);
+        assert_eq!(grid.rows_in(6..usize::MAX).next_back(), Some((TILE_H * 3, TILE_H * 3 + 1)));
+    }
+
+    #[test]
+    fn equality_ignores_tiles() {
+        let mut a = Grid::default();
+        a.push_line("a".chars().map(Glyph::Char));
+
+        let mut b = a.clone();
+        b.set(Vec2::new(TILE_W * 2, 0), Glyph::Char('x'));
+        b.truncate_line(0, 1);
+
+        assert_eq!(a, b);
+        b.set(Vec2::new(0, 0), Glyph::Char('b'));
+        assert_ne!(a, b);
+    }
+}
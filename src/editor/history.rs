@@ -0,0 +1,528 @@
+use cursive::Vec2;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    io::{self, BufRead, ErrorKind, Write},
+    iter,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::editor::{buffer::Buffer, cell::Glyph};
+
+/// A single committed cell modification, along with the value it replaced.
+///
+/// `None` marks a position past the end of its line.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct CellDelta {
+    pub pos: Vec2,
+    pub old: Option<Glyph>,
+    pub new: Option<Glyph>,
+}
+
+/// All cell modifications performed by a single undoable edit, in the order they were
+/// applied.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Delta {
+    pub cells: Vec<CellDelta>,
+    /// Number of lines in the buffer before and after the edit.
+    pub lines: (usize, usize),
+}
+
+impl Delta {
+    /// Returns `true` if applying this delta would not modify a buffer.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.cells.is_empty() && self.lines.0 == self.lines.1
+    }
+}
+
+/// One state in the undo tree.
+#[derive(Clone, Debug)]
+struct Node {
+    parent: Option<usize>,
+    /// The edit leading from the parent's state to this one. Empty for the root.
+    delta: Delta,
+    /// The child that `redo` moves to, which is the most recently visited one.
+    redo: Option<usize>,
+    /// Seconds since the unix epoch at which this state was reached.
+    time: u64,
+    desc: String,
+}
+
+/// A summary of one state in the undo tree, for display.
+#[derive(Clone, Debug)]
+pub(crate) struct UndoState {
+    pub seq: usize,
+    pub time: u64,
+    pub desc: String,
+    pub changes: usize,
+    pub current: bool,
+}
+
+/// Undo tree, storing every state the buffer has been in as a delta against its parent.
+///
+/// States are numbered in the order they were created, so `older` and `newer` walk
+/// through them chronologically across branches, while `undo` and `redo` walk up and
+/// down the current branch.
+pub(crate) struct History {
+    nodes: BTreeMap<usize, Node>,
+    root: usize,
+    current: usize,
+    next: usize,
+    depth: usize,
+}
+
+impl History {
+    /// Returns an empty history that keeps at most `depth` undo steps on the current
+    /// branch.
+    pub(crate) fn new(depth: usize) -> Self {
+        let root = Node {
+            parent: None,
+            delta: Delta::default(),
+            redo: None,
+            time: now(),
+            desc: "original".into(),
+        };
+
+        Self {
+            nodes: iter::once((0, root)).collect(),
+            root: 0,
+            current: 0,
+            next: 1,
+            depth,
+        }
+    }
+
+    /// Forget all recorded edits.
+    pub(crate) fn clear(&mut self) {
+        *self = Self::new(self.depth);
+    }
+
+    /// Record `delta` as a new state below the current one, described by `desc`.
+    ///
+    /// Any states that could previously have been redone are kept on their own branch.
+    pub(crate) fn push<S: Into<String>>(&mut self, delta: Delta, desc: S) {
+        let seq = self.next;
+        self.next += 1;
+
+        self.nodes.insert(seq, Node {
+            parent: Some(self.current),
+            delta,
+            redo: None,
+            time: now(),
+            desc: desc.into(),
+        });
+        self.node_mut(self.current).redo = Some(seq);
+        self.current = seq;
+
+        self.prune();
+    }
+
+    /// Revert the edit that led to the current state on `buf`, moving to its parent.
+    ///
+    /// Returns `false` if there was nothing to undo.
+    pub(crate) fn undo(&mut self, buf: &mut Buffer) -> bool {
+        let node = &self.nodes[&self.current];
+        let parent = match node.parent {
+            Some(p) => p,
+            None => return false,
+        };
+
+        buf.apply_delta(&node.delta, true);
+        self.node_mut(parent).redo = Some(self.current);
+        self.current = parent;
+        true
+    }
+
+    /// Reapply the most recently undone edit below the current state on `buf`.
+    ///
+    /// Returns `false` if there was nothing to redo.
+    pub(crate) fn redo(&mut self, buf: &mut Buffer) -> bool {
+        let child = match self.nodes[&self.current].redo {
+            Some(c) => c,
+            None => return false,
+        };
+
+        buf.apply_delta(&self.nodes[&child].delta, false);
+        self.current = child;
+        true
+    }
+
+    /// Move `buf` to the state created just before the current one, on any branch.
+    ///
+    /// Returns `false` if there is no older state.
+    pub(crate) fn older(&mut self, buf: &mut Buffer) -> bool {
+        match self.nodes.range(..self.current).next_back() {
+            Some((&seq, _)) => self.jump(buf, seq),
+            None => false,
+        }
+    }
+
+    /// Move `buf` to the state created just after the current one, on any branch.
+    ///
+    /// Returns `false` if there is no newer state.
+    pub(crate) fn newer(&mut self, buf: &mut Buffer) -> bool {
+        match self.nodes.range(self.current + 1..).next() {
+            Some((&seq, _)) => self.jump(buf, seq),
+            None => false,
+        }
+    }
+
+    /// Move `buf` to the state numbered `seq`, undoing up to the closest common ancestor
+    /// and redoing down the target's branch.
+    ///
+    /// Returns `false` if there is no such state, or it is already the current one.
+    pub(crate) fn jump(&mut self, buf: &mut Buffer, seq: usize) -> bool {
+        if seq == self.current || !self.nodes.contains_key(&seq) {
+            return false;
+        }
+
+        let target: Vec<_> = self.ancestors(seq).collect();
+        let on_target: HashSet<_> = target.iter().copied().collect();
+
+        while !on_target.contains(&self.current) {
+            self.undo(buf);
+        }
+
+        let split = target.iter().position(|&n| n == self.current).unwrap_or(0);
+        for &n in target[..split].iter().rev() {
+            let parent = self.current;
+            self.node_mut(parent).redo = Some(n);
+            self.redo(buf);
+        }
+
+        true
+    }
+
+    /// Returns a summary of every state in the tree, oldest first.
+    pub(crate) fn states(&self) -> Vec<UndoState> {
+        self.nodes
+            .iter()
+            .map(|(&seq, node)| UndoState {
+                seq,
+                time: node.time,
+                desc: node.desc.clone(),
+                changes: node.delta.cells.len(),
+                current: seq == self.current,
+            })
+            .collect()
+    }
+
+    fn node_mut(&mut self, seq: usize) -> &mut Node {
+        self.nodes.get_mut(&seq).expect("undo state exists")
+    }
+
+    /// Returns `seq` followed by each of its ancestors, up to and including the root.
+    fn ancestors(&self, seq: usize) -> impl Iterator<Item = usize> + '_ {
+        iter::successors(Some(seq), move |n| self.nodes[n].parent)
+    }
+
+    /// Drop the oldest states until the current branch is at most `depth` steps long.
+    ///
+    /// The tree is re-rooted at the first state on the current branch, which discards
+    /// any other branches that split off from the old root.
+    fn prune(&mut self) {
+        let path: Vec<_> = self.ancestors(self.current).collect();
+        if path.len() <= self.depth + 1 {
+            return;
+        }
+
+        let root = path[self.depth];
+
+        let mut children: HashMap<usize, Vec<usize>> = HashMap::new();
+        for (&seq, node) in &self.nodes {
+            if let Some(p) = node.parent {
+                children.entry(p).or_default().push(seq);
+            }
+        }
+
+        let mut keep = HashSet::new();
+        let mut stack = vec![root];
+        while let Some(n) = stack.pop() {
+            keep.insert(n);
+            stack.extend(children.get(&n).into_iter().flatten());
+        }
+
+        self.nodes.retain(|seq, _| keep.contains(seq));
+
+        let node = self.node_mut(root);
+        node.parent = None;
+        node.delta = Delta::default();
+        self.root = root;
+    }
+
+    /// Write the whole tree to `w`, in a format understood by `decode`.
+    pub(crate) fn encode<W: Write>(&self, mut w: W) -> io::Result<()> {
+        let seq = |s: Option<usize>| s.map_or("-".to_string(), |s| s.to_string());
+
+        writeln!(w, "tree {} {} {} {}", self.root, self.current, self.next, self.nodes.len())?;
+        for (&n, node) in &self.nodes {
+            writeln!(w, "node {} {} {} {} {}", n, seq(node.parent), seq(node.redo), node.time, node.desc)?;
+            encode_delta(&mut w, &node.delta)?;
+        }
+
+        Ok(())
+    }
+
+    /// Read a tree written by `encode` from `r`, keeping at most `depth` undo steps on
+    /// the current branch.
+    pub(crate) fn decode<R: BufRead>(r: R, depth: usize) -> io::Result<Self> {
+        let mut lines = r.lines();
+
+        let (root, current, next, len) = match next_fields(&mut lines)?.as_slice() {
+            [tag, root, current, next, len] if tag == "tree" => (
+                parse_num(root)?,
+                parse_num(current)?,
+                parse_num(next)?,
+                parse_num(len)?,
+            ),
+            _ => return Err(invalid("expected tree header")),
+        };
+
+        let mut nodes = BTreeMap::new();
+        for _ in 0..len {
+            let line = lines
+                .next()
+                .ok_or_else(|| io::Error::from(ErrorKind::UnexpectedEof))??;
+
+            let fields: Vec<_> = line.splitn(6, ' ').collect();
+            let (seq, node) = match fields.as_slice() {
+                ["node", seq, parent, redo, time, desc] => (parse_num(seq)?, Node {
+                    parent: parse_seq(parent)?,
+                    delta: Delta::default(),
+                    redo: parse_seq(redo)?,
+                    time: parse_num(time)?,
+                    desc: desc.to_string(),
+                }),
+                _ => return Err(invalid("expected undo state")),
+            };
+
+            nodes.insert(seq, Node {
+                delta: decode_delta(&mut lines)?,
+                ..node
+            });
+        }
+
+        // parents are always created before their children, which rules out cycles
+        let linked = nodes.iter().all(|(&seq, n)| {
+            let parent = match n.parent {
+                Some(p) => p < seq && nodes.contains_key(&p),
+                None => seq == root,
+            };
+            parent && n.redo.is_none_or(|r| nodes.get(&r).is_some_and(|c| c.parent == Some(seq)))
+        });
+        if !linked || !nodes.contains_key(&root) || !nodes.contains_key(&current) {
+            return Err(invalid("undo tree is not connected"));
+        }
+
+        let mut history = Self {
+            nodes,
+            root,
+            current,
+            next,
+            depth,
+        };
+        history.prune();
+
+        Ok(history)
+    }
+}
+
+/// Returns the current time, in seconds since the unix epoch.
+pub(crate) fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn encode_delta<W: Write>(w: &mut W, delta: &Delta) -> io::Result<()> {
+    writeln!(w, "delta {} {} {}", delta.lines.0, delta.lines.1, delta.cells.len())?;
+    for cd in &delta.cells {
+        writeln!(w, "{} {} {} {}", cd.pos.x, cd.pos.y, encode_cell(&cd.old), encode_cell(&cd.new))?;
+    }
+
+    Ok(())
+}
+
+fn decode_delta<I: Iterator<Item = io::Result<String>>>(lines: &mut I) -> io::Result<Delta> {
+    let header = next_fields(lines)?;
+    let (before, after, len) = match header.as_slice() {
+        [tag, before, after, len] if tag == "delta" => (
+            parse_num(before)?,
+            parse_num(after)?,
+            parse_num(len)?,
+        ),
+        _ => return Err(invalid("expected delta header")),
+    };
+
+    let mut cells = Vec::with_capacity(len);
+    for _ in 0..len {
+        let cd = match next_fields(lines)?.as_slice() {
+            [x, y, old, new] => CellDelta {
+                pos: Vec2::new(parse_num(x)?, parse_num(y)?),
+                old: parse_cell(old)?,
+                new: parse_cell(new)?,
+            },
+            _ => return Err(invalid("expected cell delta")),
+        };
+        cells.push(cd);
+    }
+
+    Ok(Delta {
+        cells,
+        lines: (before, after),
+    })
+}
+
+fn next_fields<I: Iterator<Item = io::Result<String>>>(lines: &mut I) -> io::Result<Vec<String>> {
+    let line = lines
+        .next()
+        .ok_or_else(|| io::Error::from(ErrorKind::UnexpectedEof))??;
+
+    Ok(line.split_whitespace().map(str::to_string).collect())
+}
+
+fn parse_num<T: std::str::FromStr>(s: &str) -> io::Result<T> {
+    s.parse().map_err(|_| invalid("expected a number"))
+}
+
+fn parse_seq(s: &str) -> io::Result<Option<usize>> {
+    if s == "-" {
+        return Ok(None);
+    }
+
+    parse_num(s).map(Some)
+}
+
+/// Returns `g` written as the hex codepoints of its characters joined by `+`, `-` for
+/// a position past the end of its line, or `~` for the second column of a double
+/// width glyph.
+fn encode_cell(g: &Option<Glyph>) -> String {
+    match g {
+        None => "-".to_string(),
+        Some(Glyph::Wide) => "~".to_string(),
+        Some(g) => {
+            let cs: Vec<_> = g.clone().into_chars().map(|c| format!("{:x}", c as u32)).collect();
+            cs.join("+")
+        }
+    }
+}
+
+fn parse_cell(s: &str) -> io::Result<Option<Glyph>> {
+    match s {
+        "-" => return Ok(None),
+        "~" => return Ok(Some(Glyph::Wide)),
+        _ => {}
+    }
+
+    let cs = s
+        .split('+')
+        .map(|c| u32::from_str_radix(c, 16).ok().and_then(char::from_u32))
+        .collect::<Option<String>>()
+        .ok_or_else(|| invalid("expected a character"))?;
+
+    let mut chars = cs.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Ok(Some(Glyph::Char(c))),
+        (Some(_), Some(_)) => Ok(Some(Glyph::Cluster(cs.into()))),
+        (None, _) => Err(invalid("expected a character")),
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(buf: &Buffer) -> String {
+        buf.iter("").collect()
+    }
+
+    /// Returns a history of two edits to `buf`, the second of which is undone.
+    fn edit(buf: &mut Buffer) -> History {
+        let mut history = History::new(100);
+
+        buf.begin_delta();
+        buf.set_glyph(Vec2::new(0, 0), Glyph::Char('x'));
+        buf.set_glyph(Vec2::new(4, 1), Glyph::Char('中'));
+        buf.set_glyph(Vec2::new(5, 1), Glyph::Wide);
+        buf.set_glyph(Vec2::new(1, 2), Glyph::Cluster("👨\u{200d}👩\u{200d}👧".into()));
+        buf.set_glyph(Vec2::new(2, 2), Glyph::Wide);
+        buf.set_glyph(Vec2::new(3, 2), Glyph::Char('\u{f0000}'));
+        buf.set_glyph(Vec2::new(4, 2), Glyph::Char('\u{10fffd}'));
+        buf.flush_edits();
+        history.push(buf.end_delta(), "first");
+
+        buf.begin_delta();
+        buf.set_glyph(Vec2::new(1, 0), Glyph::Char('+'));
+        buf.flush_edits();
+        history.push(buf.end_delta(), "second");
+        history.undo(buf);
+
+        history
+    }
+
+    #[test]
+    fn encode_decode_round_trip() {
+        let mut buf = Buffer::read_from("ab\ncd\n\n".as_bytes(), 8).unwrap();
+        let original = text(&buf);
+        let history = edit(&mut buf);
+        let edited = text(&buf);
+
+        let mut encoded = vec![];
+        history.encode(&mut encoded).unwrap();
+        let mut decoded = History::decode(encoded.as_slice(), 100).unwrap();
+
+        let mut reencoded = vec![];
+        decoded.encode(&mut reencoded).unwrap();
+        assert_eq!(String::from_utf8(encoded).unwrap(), String::from_utf8(reencoded).unwrap());
+
+        assert!(decoded.undo(&mut buf));
+        assert_eq!(text(&buf), original);
+        assert!(!decoded.undo(&mut buf));
+
+        assert!(decoded.redo(&mut buf));
+        assert_eq!(text(&buf), edited);
+        assert!(decoded.redo(&mut buf));
+        assert_eq!(text(&buf), "x+\ncd  中\n 👨\u{200d}👩\u{200d}👧\u{f0000}\u{10fffd}\n");
+    }
+
+    #[test]
+    fn decode_rejects_broken_trees() {
+        let mut buf = Buffer::read_from("ab\n".as_bytes(), 8).unwrap();
+        let mut encoded = vec![];
+        edit(&mut buf).encode(&mut encoded).unwrap();
+        let encoded = String::from_utf8(encoded).unwrap();
+
+        let truncated = &encoded[..encoded.len() / 2];
+        assert!(History::decode(truncated.as_bytes(), 100).is_err());
+
+        assert!(encoded.contains("node 2 1 "));
+        let orphaned = encoded.replacen("node 2 1 ", "node 2 7 ", 1);
+        assert!(History::decode(orphaned.as_bytes(), 100).is_err());
+
+        assert!(History::decode("tree 0 0 1 1\nnode 0 - - 0 original\nbogus\n".as_bytes(), 100).is_err());
+    }
+
+    #[test]
+    fn cells_round_trip() {
+        let cells = [
+            None,
+            Some(Glyph::Char(' ')),
+            Some(Glyph::Char('-')),
+            Some(Glyph::Char('+')),
+            Some(Glyph::Char('~')),
+            Some(Glyph::Char('\u{f0000}')),
+            Some(Glyph::Char('\u{10fffd}')),
+            Some(Glyph::Cluster("e\u{301}".into())),
+            Some(Glyph::Wide),
+        ];
+
+        for cell in cells {
+            assert_eq!(parse_cell(&encode_cell(&cell)).unwrap(), cell);
+        }
+        assert!(parse_cell("zz").is_err());
+    }
+}
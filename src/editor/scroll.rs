@@ -16,12 +16,18 @@ use std::cmp::{max, min};
 use crate::constants::{
     CONSUMED,
     KEY_UNDO,
+    KEY_UNDO_OLDER,
+    KEY_UNDO_NEWER,
+    KEY_UNDO_LIST,
     KEY_SAVE,
     KEY_SAVE_AS,
     KEY_CLIP,
     KEY_CLIP_PREFIX,
+    KEY_EXPORT_SVG,
+    KEY_EXPORT_HTML,
     KEY_NEW,
     KEY_OPEN,
+    KEY_OPEN_BLOCK,
     KEY_QUIT,
     KEY_DEBUG,
     KEY_CYCLE_PATH,
@@ -38,8 +44,22 @@ use crate::constants::{
     KEY_TOOL_BOX,
     KEY_TOOL_ARROW,
     KEY_TOOL_TEXT,
+    KEY_TOOL_LABEL,
+    KEY_TOOL_POLYLINE,
     KEY_TOOL_ERASE,
     KEY_TOOL_MOVE,
+    KEY_TOOL_MOVE_CONNECTED,
+    KEY_YANK,
+    KEY_SELECT_SHAPE,
+    KEY_DELETE_SHAPE,
+    KEY_RESIZE,
+    KEY_DUPLICATE,
+    KEY_ROTATE,
+    KEY_FLIP_H,
+    KEY_FLIP_V,
+    KEY_FLIP_BEND,
+    KEY_PUT,
+    KEY_REGISTER,
     KEY_TOOL_SELECT,
     KEY_TOOL_LINE,
 };
@@ -47,11 +67,13 @@ use crate::constants::{
 use crate::tools::lines::boxtool::{draw_box_on_buffer, BoxTool};
 use crate::tools::lines::arrowtool::{draw_arrow_on_buffer, ArrowTool};
 use crate::tools::lines::linetool::{draw_line_on_buffer, LineTool};
+use crate::tools::lines::polylinetool::PolylineTool;
 use crate::tools::erasetool::erase_on_buffer;
 use crate::tools::texttool::TextTool;
 use crate::tools::selecttool::SelectTool;
 use crate::tools::movetool::{MoveTool, move_on_buffer};
-use crate::tools::Tool;
+use crate::tools::transform::{Transform, transform_on_buffer};
+use crate::tools::{Bend, Tool};
 use super::{EditorView, Buffer, EditorMode};
 use cursive::Rect;
 
@@ -159,6 +181,26 @@ impl<'a> EditorCtx<'a> {
 
     /// Handles an event using the active tool.
     pub fn on_event(&mut self, event: &Event) -> Option<EventResult> {
+        // Register prefix ("x), for the next yank or put. Only the very next key names
+        // the register, and Esc or any other key that is not a character cancels it
+        {
+            let mut editor = self.0.get_inner_mut().write();
+            if editor.awaiting_register {
+                editor.awaiting_register = false;
+                match event {
+                    Event::Char(c) => {
+                        editor.register = Some(*c);
+                        return CONSUMED;
+                    }
+                    Event::Key(Key::Esc) => return CONSUMED,
+                    _ => {}
+                }
+            } else if *event == Event::Char(KEY_REGISTER) && editor.mode != EditorMode::Text {
+                editor.awaiting_register = true;
+                return CONSUMED;
+            }
+        }
+
         intercept_scrollbar!(self, event);
         intercept_pan!(self, event);
 
@@ -166,7 +208,7 @@ impl<'a> EditorCtx<'a> {
 
         // Tool Delegation (Highest Priority)
         if !matches!(mode, EditorMode::Normal) {
-            let mut tool_opt = self.0.get_inner_mut().write().active_tool.take();
+            let tool_opt = self.0.get_inner_mut().write().active_tool.take();
             if let Some(mut tool) = tool_opt {
                 let res = tool.on_event(self, event);
                 
@@ -177,7 +219,7 @@ impl<'a> EditorCtx<'a> {
                 }
 
                 if let Event::Mouse { event: Release(Left), .. } = event {
-                    if !matches!(editor.mode, EditorMode::Select(_)) {
+                    if !matches!(editor.mode, EditorMode::Select(_) | EditorMode::Polyline) {
                         editor.mode = EditorMode::Normal;
                         editor.pending_count.clear();
                     }
@@ -215,11 +257,7 @@ impl<'a> EditorCtx<'a> {
                         let mut editor = self.0.get_inner_mut().write();
                         editor.pending_count.clear();
                         let mut pos = editor.buffer.get_cursor().unwrap_or_else(|| Vec2::new(0, 0));
-                        if let Some(line) = editor.buffer.chars.get(pos.y) {
-                            pos.x = line.iter().position(|&c| !c.is_whitespace()).unwrap_or(0);
-                        } else {
-                            pos.x = 0;
-                        }
+                        pos.x = editor.buffer.first_visible(pos.y).unwrap_or(0);
                         editor.buffer.set_cursor(pos);
                         drop(editor);
                         self.scroll_to_cursor();
@@ -229,18 +267,16 @@ impl<'a> EditorCtx<'a> {
                         let mut editor = self.0.get_inner_mut().write();
                         editor.pending_count.clear();
                         let mut pos = editor.buffer.get_cursor().unwrap_or_else(|| Vec2::new(0, 0));
-                        if let Some(line) = editor.buffer.chars.get(pos.y) {
-                            pos.x = line.iter().rposition(|&c| !c.is_whitespace()).map(|p| p).unwrap_or(0);
-                        } else {
-                            pos.x = 0;
-                        }
+                        pos.x = editor.buffer.last_visible(pos.y).unwrap_or(0);
                         editor.buffer.set_cursor(pos);
                         drop(editor);
                         self.scroll_to_cursor();
                         return CONSUMED;
                     }
-                    KEY_UNDO | KEY_SAVE | KEY_SAVE_AS | KEY_CLIP | KEY_CLIP_PREFIX | KEY_NEW
-                    | KEY_OPEN | KEY_QUIT | KEY_DEBUG | KEY_CYCLE_PATH | KEY_TRIM_MARGINS | KEY_HELP => {
+                    KEY_UNDO | KEY_UNDO_OLDER | KEY_UNDO_NEWER | KEY_UNDO_LIST
+                    | KEY_SAVE | KEY_SAVE_AS | KEY_CLIP | KEY_CLIP_PREFIX | KEY_EXPORT_SVG | KEY_EXPORT_HTML
+                    | KEY_NEW | KEY_OPEN | KEY_OPEN_BLOCK | KEY_QUIT | KEY_DEBUG | KEY_CYCLE_PATH
+                    | KEY_TRIM_MARGINS | KEY_PUT | KEY_HELP => {
                         return None;
                     }
                     KEY_MOVE_LEFT | KEY_MOVE_DOWN | KEY_MOVE_UP | KEY_MOVE_RIGHT => {
@@ -259,14 +295,15 @@ impl<'a> EditorCtx<'a> {
                          }
                          editor.buffer.set_cursor(pos);
                          let opts_path_mode = editor.opts.path_mode;
+                         let bend = editor.bend(start, pos);
                          let symbols = editor.opts.symbols.clone();
                          drop(editor);
                          self.scroll_to_cursor();
                          
                          match mode {
                              EditorMode::Box(_) => self.preview(|buf| draw_box_on_buffer(buf, start, pos, &symbols)),
-                             EditorMode::Arrow(_) => self.preview(|buf| draw_arrow_on_buffer(buf, start, pos, opts_path_mode, &symbols)),
-                             EditorMode::Line(_) => self.preview(|buf| draw_line_on_buffer(buf, start, pos, opts_path_mode, &symbols)),
+                             EditorMode::Arrow(_) => self.preview(|buf| draw_arrow_on_buffer(buf, start, pos, opts_path_mode, bend, &symbols)),
+                             EditorMode::Line(_) => self.preview(|buf| draw_line_on_buffer(buf, start, pos, opts_path_mode, bend, &symbols)),
                              EditorMode::Select(_) => self.preview(|_| ()), 
                              _ => {}
                          }
@@ -277,11 +314,27 @@ impl<'a> EditorCtx<'a> {
                         editor.mut_opts(|o| o.cycle_path_mode());
                         let pos = editor.buffer.get_cursor().unwrap_or_else(|| Vec2::new(0, 0));
                         let opts_path_mode = editor.opts.path_mode;
+                        let bend = editor.bend(start, pos);
+                        let symbols = editor.opts.symbols.clone();
+                        drop(editor);
+                        match mode {
+                            EditorMode::Arrow(_) => self.preview(|buf| draw_arrow_on_buffer(buf, start, pos, opts_path_mode, bend, &symbols)),
+                            EditorMode::Line(_) => self.preview(|buf| draw_line_on_buffer(buf, start, pos, opts_path_mode, bend, &symbols)),
+                            _ => {}
+                        }
+                        return CONSUMED;
+                    }
+                    KEY_FLIP_BEND if matches!(mode, EditorMode::Arrow(_) | EditorMode::Line(_)) => {
+                        let mut editor = self.0.get_inner_mut().write();
+                        let pos = editor.buffer.get_cursor().unwrap_or_else(|| Vec2::new(0, 0));
+                        editor.flip_bend(start, pos);
+                        let bend = editor.bend(start, pos);
+                        let opts_path_mode = editor.opts.path_mode;
                         let symbols = editor.opts.symbols.clone();
                         drop(editor);
                         match mode {
-                            EditorMode::Arrow(_) => self.preview(|buf| draw_arrow_on_buffer(buf, start, pos, opts_path_mode, &symbols)),
-                            EditorMode::Line(_) => self.preview(|buf| draw_line_on_buffer(buf, start, pos, opts_path_mode, &symbols)),
+                            EditorMode::Arrow(_) => self.preview(|buf| draw_arrow_on_buffer(buf, start, pos, opts_path_mode, bend, &symbols)),
+                            EditorMode::Line(_) => self.preview(|buf| draw_line_on_buffer(buf, start, pos, opts_path_mode, bend, &symbols)),
                             _ => {}
                         }
                         return CONSUMED;
@@ -289,7 +342,7 @@ impl<'a> EditorCtx<'a> {
                     KEY_TOOL_ERASE if matches!(mode, EditorMode::Select(_)) => {
                         let end = self.0.get_inner_mut().read().buffer.get_cursor().unwrap_or_else(|| Vec2::new(0, 0));
                         let symbols = self.0.get_inner_mut().read().opts.symbols.clone();
-                        self.clobber(|buf| erase_on_buffer(buf, start, end, &symbols));
+                        self.clobber("Erase", |buf| erase_on_buffer(buf, start, end, &symbols));
                         let mut editor = self.0.get_inner_mut().write();
                         editor.mode = EditorMode::Select(end);
                         editor.buffer.discard_edits();
@@ -297,35 +350,80 @@ impl<'a> EditorCtx<'a> {
                         editor.buffer.set_cursor(end);
                         return CONSUMED;
                     }
-                    KEY_TOOL_MOVE if matches!(mode, EditorMode::Select(_)) => {
+                    KEY_YANK if matches!(mode, EditorMode::Select(_)) => {
+                        let mut editor = self.0.get_inner_mut().write();
+                        let end = editor.buffer.get_cursor().unwrap_or_else(|| Vec2::new(0, 0));
+                        editor.yank(Rect::from_corners(start, end));
+                        editor.pending_count.clear();
+                        return CONSUMED;
+                    }
+                    KEY_DUPLICATE if matches!(mode, EditorMode::Select(_)) => {
+                        let mut editor = self.0.get_inner_mut().write();
+                        let end = editor.buffer.get_cursor().unwrap_or_else(|| Vec2::new(0, 0));
+                        editor.duplicate(Rect::from_corners(start, end));
+                        editor.pending_count.clear();
+                        return CONSUMED;
+                    }
+                    KEY_SELECT_SHAPE if matches!(mode, EditorMode::Select(_)) => {
+                        let mut editor = self.0.get_inner_mut().write();
+                        let end = editor.buffer.get_cursor().unwrap_or_else(|| Vec2::new(0, 0));
+                        editor.select_shape(Some(Rect::from_corners(start, end)));
+                        editor.pending_count.clear();
+                        return CONSUMED;
+                    }
+                    KEY_ROTATE | KEY_FLIP_H | KEY_FLIP_V if matches!(mode, EditorMode::Select(_)) => {
+                        let end = self.0.get_inner_mut().read().buffer.get_cursor().unwrap_or_else(|| Vec2::new(0, 0));
+                        let symbols = self.0.get_inner_mut().read().opts.symbols.clone();
+                        let transform = match *c {
+                            KEY_ROTATE => Transform::Rotate,
+                            KEY_FLIP_H => Transform::FlipH,
+                            _ => Transform::FlipV,
+                        };
+                        let area = Rect::from_corners(start, end);
+                        let moved = transform.apply_rect(area);
+                        self.clobber(&transform.to_string(), |buf| {
+                            transform_on_buffer(buf, area, transform, &symbols);
+                            buf.set_cursor(moved.bottom_right());
+                        });
+                        let mut editor = self.0.get_inner_mut().write();
+                        editor.mode = EditorMode::Select(moved.top_left());
+                        editor.pending_count.clear();
+                        return CONSUMED;
+                    }
+                    KEY_TOOL_MOVE | KEY_TOOL_MOVE_CONNECTED if matches!(mode, EditorMode::Select(_)) => {
                         let end = self.0.get_inner_mut().read().buffer.get_cursor().unwrap_or_else(|| Vec2::new(0, 0));
                         let mut editor = self.0.get_inner_mut().write();
                         let selection = Rect::from_corners(start, end);
                         let anchor = end;
                         let symbols = editor.opts.symbols.clone();
-                        let tool = MoveTool::new(selection, anchor);
+                        let tool = match *c {
+                            KEY_TOOL_MOVE_CONNECTED => MoveTool::rubber_band(selection, anchor),
+                            _ => MoveTool::new(selection, anchor),
+                        };
                         editor.set_tool(tool);
                         editor.pending_count.clear();
                         drop(editor);
                         self.preview(|buf| move_on_buffer(buf, selection, anchor, anchor, &symbols));
                         return CONSUMED;
                     }
-                    KEY_TOOL_BOX | KEY_TOOL_ARROW | KEY_TOOL_LINE | KEY_TOOL_SELECT | KEY_TOOL_TEXT | '\n' => {
+                    KEY_TOOL_BOX | KEY_TOOL_ARROW | KEY_TOOL_LINE | KEY_TOOL_SELECT | KEY_TOOL_TEXT | KEY_TOOL_LABEL | '\n' => {
                         let end = self.0.get_inner_mut().read().buffer.get_cursor().unwrap_or_else(|| Vec2::new(0, 0));
                         let mut editor = self.0.get_inner_mut().write();
                         let opts_path_mode = editor.opts.path_mode;
+                        let bend = editor.bend(start, end);
                         let symbols = editor.opts.symbols.clone();
                         drop(editor);
 
                         match mode {
-                            EditorMode::Box(_) => self.clobber(|buf| draw_box_on_buffer(buf, start, end, &symbols)),
-                            EditorMode::Arrow(_) => self.clobber(|buf| draw_arrow_on_buffer(buf, start, end, opts_path_mode, &symbols)),
-                            EditorMode::Line(_) => self.clobber(|buf| draw_line_on_buffer(buf, start, end, opts_path_mode, &symbols)),
+                            EditorMode::Box(_) => self.clobber("Box", |buf| draw_box_on_buffer(buf, start, end, &symbols)),
+                            EditorMode::Arrow(_) => self.clobber("Arrow", |buf| draw_arrow_on_buffer(buf, start, end, opts_path_mode, bend, &symbols)),
+                            EditorMode::Line(_) => self.clobber("Line", |buf| draw_line_on_buffer(buf, start, end, opts_path_mode, bend, &symbols)),
                             _ => {}
                         }
                         
                         let mut editor = self.0.get_inner_mut().write();
                         editor.buffer.discard_edits();
+                        editor.forget_bend();
                         editor.pending_count.clear();
                         
                         let cursor_pos = if editor.opts.box_cursor_start { start } else { end };
@@ -346,7 +444,7 @@ impl<'a> EditorCtx<'a> {
                                 tool.load_opts(&editor.opts);
                                 editor.set_tool(tool);
                                 drop(editor);
-                                self.preview(|buf| draw_arrow_on_buffer(buf, cursor_pos, cursor_pos, opts_path_mode, &symbols));
+                                self.preview(|buf| draw_arrow_on_buffer(buf, cursor_pos, cursor_pos, opts_path_mode, Bend::default(), &symbols));
                             }
                             KEY_TOOL_LINE if !matches!(mode, EditorMode::Line(_)) => {
                                 editor.mode = EditorMode::Line(cursor_pos);
@@ -354,7 +452,7 @@ impl<'a> EditorCtx<'a> {
                                 tool.load_opts(&editor.opts);
                                 editor.set_tool(tool);
                                 drop(editor);
-                                self.preview(|buf| draw_line_on_buffer(buf, cursor_pos, cursor_pos, opts_path_mode, &symbols));
+                                self.preview(|buf| draw_line_on_buffer(buf, cursor_pos, cursor_pos, opts_path_mode, Bend::default(), &symbols));
                             }
                             KEY_TOOL_SELECT => {
                                 editor.mode = EditorMode::Select(cursor_pos);
@@ -366,8 +464,13 @@ impl<'a> EditorCtx<'a> {
                                 editor.mode = EditorMode::Text;
                                 let mut tool = TextTool::new(cursor_pos);
                                 tool.load_opts(&editor.opts);
+                                tool.attach(&editor.buffer);
                                 editor.set_tool(tool);
                             }
+                            KEY_TOOL_LABEL => {
+                                editor.mode = EditorMode::Text;
+                                editor.set_tool(TextTool::label(cursor_pos));
+                            }
                             _ => {
                                 editor.mode = EditorMode::Normal;
                             }
@@ -389,40 +492,44 @@ impl<'a> EditorCtx<'a> {
                  return CONSUMED;
              } else if let Event::Key(Key::Esc) = event {
                  let end = self.0.get_inner_mut().read().buffer.get_cursor().unwrap_or_else(|| Vec2::new(0, 0));
-                 let editor = self.0.get_inner_mut().write();
+                 let mut editor = self.0.get_inner_mut().write();
                  let opts_path_mode = editor.opts.path_mode;
+                 let bend = editor.bend(start, end);
                  let symbols = editor.opts.symbols.clone();
                  drop(editor);
 
                  match mode {
-                     EditorMode::Box(_) => self.clobber(|buf| draw_box_on_buffer(buf, start, end, &symbols)),
-                     EditorMode::Arrow(_) => self.clobber(|buf| draw_arrow_on_buffer(buf, start, end, opts_path_mode, &symbols)),
-                     EditorMode::Line(_) => self.clobber(|buf| draw_line_on_buffer(buf, start, end, opts_path_mode, &symbols)),
+                     EditorMode::Box(_) => self.clobber("Box", |buf| draw_box_on_buffer(buf, start, end, &symbols)),
+                     EditorMode::Arrow(_) => self.clobber("Arrow", |buf| draw_arrow_on_buffer(buf, start, end, opts_path_mode, bend, &symbols)),
+                     EditorMode::Line(_) => self.clobber("Line", |buf| draw_line_on_buffer(buf, start, end, opts_path_mode, bend, &symbols)),
                      _ => {} 
                  }
 
                  let mut editor = self.0.get_inner_mut().write();
                  editor.mode = EditorMode::Normal;
                  editor.buffer.discard_edits();
+                 editor.forget_bend();
                  editor.pending_count.clear();
                  return CONSUMED;
              } else if let Event::Key(Key::Enter) = event {
                  let end = self.0.get_inner_mut().read().buffer.get_cursor().unwrap_or_else(|| Vec2::new(0, 0));
-                 let editor = self.0.get_inner_mut().write();
+                 let mut editor = self.0.get_inner_mut().write();
                  let opts_path_mode = editor.opts.path_mode;
+                 let bend = editor.bend(start, end);
                  let symbols = editor.opts.symbols.clone();
                  drop(editor);
 
                  match mode {
-                     EditorMode::Box(_) => self.clobber(|buf| draw_box_on_buffer(buf, start, end, &symbols)),
-                     EditorMode::Arrow(_) => self.clobber(|buf| draw_arrow_on_buffer(buf, start, end, opts_path_mode, &symbols)),
-                     EditorMode::Line(_) => self.clobber(|buf| draw_line_on_buffer(buf, start, end, opts_path_mode, &symbols)),
+                     EditorMode::Box(_) => self.clobber("Box", |buf| draw_box_on_buffer(buf, start, end, &symbols)),
+                     EditorMode::Arrow(_) => self.clobber("Arrow", |buf| draw_arrow_on_buffer(buf, start, end, opts_path_mode, bend, &symbols)),
+                     EditorMode::Line(_) => self.clobber("Line", |buf| draw_line_on_buffer(buf, start, end, opts_path_mode, bend, &symbols)),
                      _ => {}
                  }
                  
                  let mut editor = self.0.get_inner_mut().write();
                  editor.mode = EditorMode::Normal;
                  editor.buffer.discard_edits();
+                 editor.forget_bend();
                  editor.pending_count.clear();
                  if editor.opts.box_cursor_start {
                      editor.buffer.set_cursor(start);
@@ -462,11 +569,7 @@ impl<'a> EditorCtx<'a> {
                         let mut editor = self.0.get_inner_mut().write();
                         editor.pending_count.clear();
                         let mut pos = editor.buffer.get_cursor().unwrap_or_else(|| Vec2::new(0, 0));
-                        if let Some(line) = editor.buffer.chars.get(pos.y) {
-                            pos.x = line.iter().position(|&c| !c.is_whitespace()).unwrap_or(0);
-                        } else {
-                            pos.x = 0;
-                        }
+                        pos.x = editor.buffer.first_visible(pos.y).unwrap_or(0);
                         editor.buffer.set_cursor(pos);
                         drop(editor);
                         self.scroll_to_cursor();
@@ -476,11 +579,7 @@ impl<'a> EditorCtx<'a> {
                         let mut editor = self.0.get_inner_mut().write();
                         editor.pending_count.clear();
                         let mut pos = editor.buffer.get_cursor().unwrap_or_else(|| Vec2::new(0, 0));
-                        if let Some(line) = editor.buffer.chars.get(pos.y) {
-                            pos.x = line.iter().rposition(|&c| !c.is_whitespace()).map(|p| p).unwrap_or(0);
-                        } else {
-                            pos.x = 0;
-                        }
+                        pos.x = editor.buffer.last_visible(pos.y).unwrap_or(0);
                         editor.buffer.set_cursor(pos);
                         drop(editor);
                         self.scroll_to_cursor();
@@ -529,7 +628,7 @@ impl<'a> EditorCtx<'a> {
                         let opts_path_mode = editor.opts.path_mode;
                         let symbols = editor.opts.symbols.clone();
                         drop(editor);
-                        self.preview(|buf| draw_arrow_on_buffer(buf, pos, pos, opts_path_mode, &symbols));
+                        self.preview(|buf| draw_arrow_on_buffer(buf, pos, pos, opts_path_mode, Bend::default(), &symbols));
                         return CONSUMED;
                     }
                     KEY_TOOL_SELECT => {
@@ -549,9 +648,25 @@ impl<'a> EditorCtx<'a> {
                         editor.mode = EditorMode::Text;
                         let mut tool = TextTool::new(pos);
                         tool.load_opts(&editor.opts);
+                        tool.attach(&editor.buffer);
                         editor.set_tool(tool);
                         return CONSUMED;
                     }
+                    KEY_TOOL_LABEL => {
+                        let mut editor = self.0.get_inner_mut().write();
+                        editor.pending_count.clear();
+                        let pos = editor.buffer.get_cursor().unwrap_or(Vec2::new(0, 0));
+                        editor.mode = EditorMode::Text;
+                        editor.set_tool(TextTool::label(pos));
+                        return CONSUMED;
+                    }
+                    KEY_TOOL_POLYLINE => {
+                        let mut editor = self.0.get_inner_mut().write();
+                        editor.pending_count.clear();
+                        editor.mode = EditorMode::Polyline;
+                        editor.set_tool(PolylineTool::default());
+                        return CONSUMED;
+                    }
                     KEY_TOOL_LINE => {
                         let mut editor = self.0.get_inner_mut().write();
                         editor.pending_count.clear();
@@ -563,11 +678,31 @@ impl<'a> EditorCtx<'a> {
                         let opts_path_mode = editor.opts.path_mode;
                         let symbols = editor.opts.symbols.clone();
                         drop(editor);
-                        self.preview(|buf| draw_line_on_buffer(buf, pos, pos, opts_path_mode, &symbols));
+                        self.preview(|buf| draw_line_on_buffer(buf, pos, pos, opts_path_mode, Bend::default(), &symbols));
+                        return CONSUMED;
+                    }
+                    KEY_SELECT_SHAPE => {
+                        let mut editor = self.0.get_inner_mut().write();
+                        editor.pending_count.clear();
+                        editor.select_shape(None);
+                        return CONSUMED;
+                    }
+                    KEY_DELETE_SHAPE => {
+                        let mut editor = self.0.get_inner_mut().write();
+                        editor.pending_count.clear();
+                        editor.delete_shape();
                         return CONSUMED;
                     }
-                    KEY_UNDO | KEY_SAVE | KEY_SAVE_AS | KEY_CLIP | KEY_CLIP_PREFIX | KEY_NEW
-                    | KEY_OPEN | KEY_QUIT | KEY_DEBUG | KEY_CYCLE_PATH | KEY_TRIM_MARGINS | KEY_HELP => {
+                    KEY_RESIZE => {
+                        let mut editor = self.0.get_inner_mut().write();
+                        editor.pending_count.clear();
+                        editor.resize_box(false);
+                        return CONSUMED;
+                    }
+                    KEY_UNDO | KEY_UNDO_OLDER | KEY_UNDO_NEWER | KEY_UNDO_LIST
+                    | KEY_SAVE | KEY_SAVE_AS | KEY_CLIP | KEY_CLIP_PREFIX | KEY_EXPORT_SVG | KEY_EXPORT_HTML
+                    | KEY_NEW | KEY_OPEN | KEY_OPEN_BLOCK | KEY_QUIT | KEY_DEBUG | KEY_CYCLE_PATH
+                    | KEY_TRIM_MARGINS | KEY_PUT | KEY_HELP => {
                         let mut editor = self.0.get_inner_mut().write();
                         editor.pending_count.clear();
                         return None;
@@ -584,13 +719,30 @@ impl<'a> EditorCtx<'a> {
                     editor.pending_count.clear();
                 }
                 let rel_event = self.relativize(event);
-                if let Event::Mouse { position, .. } = rel_event {
+                if let Event::Mouse { position, event: mouse_event, .. } = rel_event {
                     let mut editor = self.0.get_inner_mut().write();
+                    if mouse_event == Press(Left) {
+                        editor.press = Some(position);
+                    }
+
+                    // dragging away from a box outline grabs it for resizing, while a
+                    // plain click only places the cursor
+                    if let Some(from) = editor.press.filter(|&from| from != position) {
+                        editor.press = None;
+                        editor.buffer.set_cursor(from);
+                        if editor.resize_box(true) {
+                            drop(editor);
+                            return self.on_event(event);
+                        }
+                    }
+
                     editor.buffer.set_cursor(position);
                     drop(editor);
                     self.scroll_to_cursor();
                     return CONSUMED;
                 }
+            } else if let Event::Mouse { event: Release(Left), .. } = event {
+                self.0.get_inner_mut().write().press = None;
             } else if let Event::Key(Key::Esc) = event {
                  let mut editor = self.0.get_inner_mut().write();
                  editor.pending_count.clear();
@@ -684,12 +836,12 @@ impl<'a> EditorCtx<'a> {
         }
     }
 
-    /// Modify the edit buffer using `render`, flushing any changes and saving a snapshot
-    /// of the buffer's prior state in the editor's undo history.
-    pub(crate) fn clobber<R: FnOnce(&mut Buffer)>(&mut self, render: R) {
+    /// Modify the edit buffer using `render`, flushing any changes and recording them in
+    /// the editor's undo history, described by `desc`.
+    pub(crate) fn clobber<R: FnOnce(&mut Buffer)>(&mut self, desc: &str, render: R) {
         let mut editor = self.0.get_inner_mut().write();
 
-        editor.with_snapshot(|ed| {
+        editor.with_history(desc, |ed| {
             render(&mut ed.buffer);
             ed.buffer.flush_edits();
         });
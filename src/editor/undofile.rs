@@ -0,0 +1,124 @@
+use std::{
+    env,
+    fs::{self, File},
+    io::{self, BufRead, BufReader, BufWriter, ErrorKind, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::editor::history::History;
+
+const MAGIC: &str = "unikode-undo 3";
+
+/// Returns the path of the undo file that belongs to the diagram at `path`.
+///
+/// Undo files live under `$XDG_STATE_HOME/unikode/undo` (falling back to
+/// `$HOME/.local/state`), named after the diagram's absolute path with `/` replaced by
+/// `%`.
+pub(crate) fn undo_file_path(path: &Path) -> Option<PathBuf> {
+    let state = env::var_os("XDG_STATE_HOME")
+        .filter(|s| !s.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|h| Path::new(&h).join(".local/state")))?;
+
+    let abs = match path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        Some(parent) => fs::canonicalize(parent).ok()?.join(path.file_name()?),
+        None => env::current_dir().ok()?.join(path),
+    };
+
+    let name = abs.to_string_lossy().replace(std::path::MAIN_SEPARATOR, "%");
+
+    Some(state.join("unikode").join("undo").join(name))
+}
+
+/// Returns a stable hash of `bytes`, used to check that a diagram has not changed since
+/// its undo file was written.
+pub(crate) fn content_hash(bytes: &[u8]) -> u64 {
+    // 64 bit FNV-1a
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |h, &b| {
+        (h ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Write `history` to the undo file at `path`, tagged with the hash of the diagram it
+/// applies to.
+pub(crate) fn write(path: &Path, hash: u64, history: &History) -> io::Result<()> {
+    path.parent().map(fs::create_dir_all).transpose()?;
+
+    let mut w = BufWriter::new(File::create(path)?);
+    writeln!(w, "{}", MAGIC)?;
+    writeln!(w, "{:016x}", hash)?;
+    history.encode(&mut w)?;
+    w.flush()
+}
+
+/// Read the history stored in the undo file at `path`.
+///
+/// Returns `Ok(None)` if there is no undo file, or if it was written for different
+/// diagram contents than those hashing to `hash`.
+pub(crate) fn read(path: &Path, hash: u64, depth: usize) -> io::Result<Option<History>> {
+    let file = match File::open(path) {
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        r => r?,
+    };
+
+    let mut r = BufReader::new(file);
+    let mut line = String::new();
+
+    r.read_line(&mut line)?;
+    if line.trim_end() != MAGIC {
+        return Ok(None);
+    }
+
+    line.clear();
+    r.read_line(&mut line)?;
+    if u64::from_str_radix(line.trim_end(), 16).ok() != Some(hash) {
+        return Ok(None);
+    }
+
+    History::decode(r, depth).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch(name: &str) -> PathBuf {
+        env::temp_dir()
+            .join(format!("unikode-test-{}", std::process::id()))
+            .join(name)
+    }
+
+    fn states(history: &History) -> Vec<(usize, String, bool)> {
+        history.states().into_iter().map(|s| (s.seq, s.desc, s.current)).collect()
+    }
+
+    #[test]
+    fn read_checks_magic_and_hash() {
+        let path = scratch("diagram.txt");
+        let hash = content_hash(b"+--+\n");
+
+        let mut history = History::new(10);
+        history.push(Default::default(), "edit");
+        write(&path, hash, &history).unwrap();
+
+        let read_back = read(&path, hash, 10).unwrap().unwrap();
+        assert_eq!(states(&read_back), states(&history));
+
+        assert!(read(&path, content_hash(b"+--+ \n"), 10).unwrap().is_none());
+
+        let stale = fs::read_to_string(&path).unwrap().replacen(MAGIC, "unikode-undo 2", 1);
+        fs::write(&path, stale).unwrap();
+        assert!(read(&path, hash, 10).unwrap().is_none());
+
+        fs::remove_file(&path).unwrap();
+        assert!(read(&path, hash, 10).unwrap().is_none());
+        fs::remove_dir(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn content_hash_is_stable() {
+        assert_eq!(content_hash(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(content_hash(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_ne!(content_hash(b"ab"), content_hash(b"ba"));
+    }
+}
@@ -0,0 +1,73 @@
+pub mod html;
+pub mod svg;
+
+use std::{fmt, str::FromStr};
+
+use crate::config::Options;
+use crate::editor::buffer::Buffer;
+
+/// Output formats that a diagram can be converted to.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) enum Format {
+    #[default]
+    Text,
+    Markdown,
+    Svg,
+    Html,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "text" | "txt" => Ok(Format::Text),
+            "markdown" | "md" => Ok(Format::Markdown),
+            "svg" => Ok(Format::Svg),
+            "html" | "htm" => Ok(Format::Html),
+            _ => Err(format!("Invalid format: '{}'", s)),
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Format::Text => write!(f, "text"),
+            Format::Markdown => write!(f, "markdown"),
+            Format::Svg => write!(f, "svg"),
+            Format::Html => write!(f, "html"),
+        }
+    }
+}
+
+/// Render the committed contents of `buf` as `format`.
+pub(crate) fn render(buf: &Buffer, format: Format, opts: &Options) -> String {
+    match format {
+        Format::Text => buf.iter("").collect(),
+        Format::Markdown => {
+            let ending = buf.line_ending().as_str();
+            let mut s = format!("```{}", ending);
+            s.extend(buf.iter(""));
+            s.push_str("```");
+            s.push_str(ending);
+            s
+        }
+        Format::Svg => svg::render(buf, &opts.symbols),
+        Format::Html => html::render(buf, opts),
+    }
+}
+
+/// Append `s` to `out`, escaped for use in XML text and attributes.
+pub(crate) fn escape_into(out: &mut String, s: &str) {
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+}
@@ -0,0 +1,53 @@
+use cursive::theme::Color;
+
+use super::escape_into;
+use crate::config::{parse_color, Options};
+use crate::editor::buffer::Buffer;
+
+/// Render the committed contents of `buf` as a self-contained HTML document, using the
+/// background and text colours from `opts`. The other theme colours only style
+/// editing state, such as unsaved changes and the cursor, which is not exported.
+///
+/// If `opts.html_collapsible` is set, the diagram is wrapped in a `<details>` block.
+pub(crate) fn render(buf: &Buffer, opts: &Options) -> String {
+    let mut style = String::from("font-family: monospace; line-height: 1.2; padding: 1em;");
+    if let Some(bg) = opts.background.as_deref().and_then(css_color) {
+        style.push_str(&format!(" background: {};", bg));
+    }
+    if let Some(fg) = opts.color_normal.as_deref().and_then(css_color) {
+        style.push_str(&format!(" color: {};", fg));
+    }
+
+    let mut pre = format!(r#"<pre style="{}">"#, style);
+    escape_into(&mut pre, &buf.iter("").collect::<String>());
+    pre.push_str("</pre>");
+
+    let body = if opts.html_collapsible {
+        format!("<details>\n<summary>Diagram</summary>\n{}\n</details>", pre)
+    } else {
+        pre
+    };
+
+    format!(
+        "<!DOCTYPE html>\n\
+         <html>\n\
+         <head>\n\
+         <meta charset=\"utf-8\">\n\
+         <title>Diagram</title>\n\
+         </head>\n\
+         <body>\n\
+         {}\n\
+         </body>\n\
+         </html>\n",
+        body,
+    )
+}
+
+/// Returns the CSS colour for the hex or "transparent" colour `s`, or `None` if there is
+/// nothing to set.
+fn css_color(s: &str) -> Option<String> {
+    match parse_color(s)? {
+        Color::Rgb(r, g, b) => Some(format!("#{:02x}{:02x}{:02x}", r, g, b)),
+        _ => None,
+    }
+}
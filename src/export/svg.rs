@@ -0,0 +1,248 @@
+use cursive::Vec2;
+use std::{collections::BTreeMap, fmt::Write};
+
+use crate::config::Symbols;
+use crate::editor::{buffer::Buffer, cell::{Char, Glyph as CellGlyph}};
+use super::escape_into;
+use crate::utils::{
+    graphemes,
+    junctions::{connects_down, connects_left, connects_right, connects_up},
+};
+
+/// Width of a cell, in user units.
+const CELL_W: usize = 10;
+/// Height of a cell, in user units.
+const CELL_H: usize = 20;
+const FONT_SIZE: usize = 16;
+
+/// How the glyph in a cell is drawn.
+enum Glyph {
+    /// Lines from the middle of the cell to its north, south, west and east edges.
+    Arms([bool; 4]),
+    /// A line from the bottom left to the top right corner.
+    Diag,
+    /// A line from the top left to the bottom right corner.
+    Gaid,
+    /// An arrow tip pointing along `(dx, dy)`, with a line from its base to the edge of
+    /// the cell if a line arrives there.
+    Tip(isize, isize, bool),
+    Text,
+}
+
+/// Render the committed contents of `buf` as a standalone SVG document.
+///
+/// Box-drawing characters from `symbols` become vector lines and arrow tips become
+/// polygons, while everything else is placed as text on a monospace grid.
+pub(crate) fn render(buf: &Buffer, symbols: &Symbols) -> String {
+    let size = buf.bounds();
+    let (w, h) = (size.x * CELL_W, size.y * CELL_H);
+
+    // half-cell line segments, merged into runs keyed by row or column
+    let mut hruns: BTreeMap<usize, Vec<(usize, usize)>> = BTreeMap::new();
+    let mut vruns: BTreeMap<usize, Vec<(usize, usize)>> = BTreeMap::new();
+    let mut diags = String::new();
+    let mut tips = String::new();
+    let mut texts: BTreeMap<usize, Vec<(usize, CellGlyph)>> = BTreeMap::new();
+
+    let cells = buf.iter_within(Vec2::zero(), size, symbols).filter_map(|c| match c {
+        Char::Clean(cell) if !cell.is_whitespace() && *cell.glyph() != CellGlyph::Wide => Some(cell),
+        _ => None,
+    });
+
+    for cell in cells {
+        let Vec2 { x, y } = cell.pos();
+        let (x0, y0) = (x * CELL_W, y * CELL_H);
+        let (cx, cy) = (x0 + CELL_W / 2, y0 + CELL_H / 2);
+
+        let shape = match *cell.glyph() {
+            CellGlyph::Char(c) => glyph(buf, cell.pos(), c, symbols),
+            _ => Glyph::Text,
+        };
+
+        match shape {
+            Glyph::Arms([n, s, w, e]) => {
+                if n { vruns.entry(cx).or_default().push((y0, cy)); }
+                if s { vruns.entry(cx).or_default().push((cy, y0 + CELL_H)); }
+                if w { hruns.entry(cy).or_default().push((x0, cx)); }
+                if e { hruns.entry(cy).or_default().push((cx, x0 + CELL_W)); }
+            }
+
+            Glyph::Diag => {
+                let _ = write!(diags, "M{} {}L{} {}", x0, y0 + CELL_H, x0 + CELL_W, y0);
+            }
+
+            Glyph::Gaid => {
+                let _ = write!(diags, "M{} {}L{} {}", x0, y0, x0 + CELL_W, y0 + CELL_H);
+            }
+
+            Glyph::Tip(dx, dy, tail) => {
+                if tail {
+                    match (dx, dy) {
+                        (0, -1) => vruns.entry(cx).or_default().push((cy, y0 + CELL_H)),
+                        (0, _) => vruns.entry(cx).or_default().push((y0, cy)),
+                        (-1, _) => hruns.entry(cy).or_default().push((cx, x0 + CELL_W)),
+                        _ => hruns.entry(cy).or_default().push((x0, cx)),
+                    }
+                }
+
+                let (hw, hh) = (CELL_W as isize / 2, CELL_H as isize / 2);
+                let (cx, cy) = (cx as isize, cy as isize);
+                // the point, followed by the two corners of the base
+                let (px, py) = (cx + dx * hw, cy + dy * hw);
+                let (bx, by) = (cx - dx * hw, cy - dy * hw);
+                let (ox, oy) = (dy.abs() * hw, dx.abs() * hh / 2);
+                let _ = write!(
+                    tips,
+                    r#"<polygon points="{},{} {},{} {},{}"/>"#,
+                    px, py, bx - ox, by - oy, bx + ox, by + oy,
+                );
+            }
+
+            Glyph::Text => texts.entry(y).or_default().push((x, cell.glyph)),
+        }
+    }
+
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}">"#,
+    );
+
+    let mut path = String::new();
+    for (y, spans) in hruns {
+        for (a, b) in merge(spans) {
+            let _ = write!(path, "M{} {}H{}", a, y, b);
+        }
+    }
+    for (x, spans) in vruns {
+        for (a, b) in merge(spans) {
+            let _ = write!(path, "M{} {}V{}", x, a, b);
+        }
+    }
+    path.push_str(&diags);
+
+    if !path.is_empty() {
+        let _ = writeln!(
+            out,
+            r#"<path d="{}" fill="none" stroke="currentColor" stroke-width="1.5" stroke-linecap="square"/>"#,
+            path,
+        );
+    }
+
+    if !tips.is_empty() {
+        let _ = writeln!(out, r#"<g fill="currentColor">{}</g>"#, tips);
+    }
+
+    if !texts.is_empty() {
+        let _ = writeln!(
+            out,
+            r#"<g font-family="monospace" font-size="{}" fill="currentColor" text-anchor="middle" dominant-baseline="central">"#,
+            FONT_SIZE,
+        );
+        for (y, row) in texts {
+            write_text_row(&mut out, y, &row);
+        }
+        let _ = writeln!(out, "</g>");
+    }
+
+    out.push_str("</svg>\n");
+    out
+}
+
+/// Returns how to draw `c`, found at `pos` in `buf`.
+fn glyph(buf: &Buffer, pos: Vec2, c: char, s: &Symbols) -> Glyph {
+    let at = |dx: isize, dy: isize| {
+        let x = pos.x.checked_add_signed(dx)?;
+        let y = pos.y.checked_add_signed(dy)?;
+        Some(buf.get_char_at(Vec2::new(x, y)))
+    };
+    let line = |c: Option<char>, f: fn(char, &Symbols) -> bool, plain: char| {
+        c.is_some_and(|c| f(c, s) || c == plain || c == s.plus)
+    };
+
+    let arms = match c {
+        c if c == s.hline || c == s.dash => [false, false, true, true],
+        c if c == s.vline || c == s.pipe => [true, true, false, false],
+        c if c == s.tlcorn => [false, true, false, true],
+        c if c == s.trcorn => [false, true, true, false],
+        c if c == s.blcorn => [true, false, false, true],
+        c if c == s.brcorn => [true, false, true, false],
+        c if c == s.lhinter => [true, true, false, true],
+        c if c == s.rhinter => [true, true, true, false],
+        c if c == s.tvinter => [false, true, true, true],
+        c if c == s.bvinter => [true, false, true, true],
+        c if c == s.cinter => [true; 4],
+
+        // plain junctions only reach towards whatever they join
+        c if c == s.plus => {
+            let arms = [
+                line(at(0, -1), connects_down, s.pipe),
+                line(at(0, 1), connects_up, s.pipe),
+                line(at(-1, 0), connects_right, s.dash),
+                line(at(1, 0), connects_left, s.dash),
+            ];
+            if arms == [false; 4] {
+                return Glyph::Text;
+            }
+            arms
+        }
+
+        c if c == s.diag || c == s.diag2 => return Glyph::Diag,
+        c if c == s.gaid || c == s.gaid2 => return Glyph::Gaid,
+        c if c == s.n => return Glyph::Tip(0, -1, line(at(0, 1), connects_up, s.pipe)),
+        c if c == s.s => return Glyph::Tip(0, 1, line(at(0, -1), connects_down, s.pipe)),
+        c if c == s.w => return Glyph::Tip(-1, 0, line(at(1, 0), connects_left, s.dash)),
+        c if c == s.e => return Glyph::Tip(1, 0, line(at(-1, 0), connects_right, s.dash)),
+        _ => return Glyph::Text,
+    };
+
+    Glyph::Arms(arms)
+}
+
+/// Returns `spans` sorted, with touching or overlapping spans joined together.
+fn merge(mut spans: Vec<(usize, usize)>) -> Vec<(usize, usize)> {
+    spans.sort_unstable();
+
+    let mut merged: Vec<(usize, usize)> = vec![];
+    for (a, b) in spans {
+        match merged.last_mut() {
+            Some(last) if a <= last.1 => last.1 = last.1.max(b),
+            _ => merged.push((a, b)),
+        }
+    }
+    merged
+}
+
+/// Write the text cells on row `y` as `<text>` elements, pinning every character to
+/// the middle of its cell.
+fn write_text_row(out: &mut String, y: usize, row: &[(usize, CellGlyph)]) {
+    let cy = y * CELL_H + CELL_H / 2;
+    let mut xs = vec![];
+    let mut run = String::new();
+
+    let flush = |out: &mut String, xs: &mut Vec<String>, run: &mut String| {
+        if !run.is_empty() {
+            let _ = writeln!(out, r#"<text x="{}" y="{}">{}</text>"#, xs.join(" "), cy, run);
+            xs.clear();
+            run.clear();
+        }
+    };
+
+    for (x, g) in row {
+        let cx = x * CELL_W + graphemes::width(g) * CELL_W / 2;
+        let cluster = g.to_string();
+
+        // x positions apply per character, so clusters made of several get their own
+        if cluster.chars().count() > 1 {
+            flush(out, &mut xs, &mut run);
+            xs.push(cx.to_string());
+            escape_into(&mut run, &cluster);
+            flush(out, &mut xs, &mut run);
+        } else {
+            xs.push(cx.to_string());
+            escape_into(&mut run, &cluster);
+        }
+    }
+
+    flush(out, &mut xs, &mut run);
+}
@@ -1,5 +1,7 @@
 #![allow(clippy::many_single_char_names)]
+mod cli;
 mod editor;
+mod export;
 mod modeline;
 mod tools;
 mod ui;
@@ -9,7 +11,7 @@ mod config;
 
 use structopt::StructOpt;
 use cursive::{
-    event::{EventTrigger, Event, Key},
+    event::{EventTrigger, Event},
     logger,
     menu::Tree,
     view::Nameable,
@@ -22,17 +24,20 @@ use std::error::Error;
 
 use crate::constants::{
     EDITOR_ID,
-    KEY_UNDO, KEY_SAVE, KEY_SAVE_AS, KEY_CLIP, KEY_CLIP_PREFIX,
-    KEY_NEW, KEY_OPEN, KEY_QUIT, KEY_DEBUG, KEY_CYCLE_PATH, KEY_TRIM_MARGINS,
+    KEY_UNDO, KEY_UNDO_OLDER, KEY_UNDO_NEWER, KEY_UNDO_LIST, KEY_SAVE, KEY_SAVE_AS, KEY_CLIP, KEY_CLIP_PREFIX,
+    KEY_EXPORT_SVG, KEY_EXPORT_HTML,
+    KEY_NEW, KEY_OPEN, KEY_OPEN_BLOCK, KEY_QUIT, KEY_DEBUG, KEY_CYCLE_PATH, KEY_TRIM_MARGINS,
     KEY_HELP,
     KEY_TOOL_BOX, KEY_TOOL_LINE, KEY_TOOL_ARROW, KEY_TOOL_TEXT, 
-    KEY_TOOL_SELECT,
+    KEY_TOOL_SELECT, KEY_PUT,
 };
 use crate::config::{Options, parse_color};
 use crate::modeline::ModeLine;
 use crate::ui::{
-    editor_new, editor_open, editor_save, editor_save_as, editor_clip,
-    editor_clip_prefix, editor_quit, editor_undo, editor_redo,
+    editor_new, editor_open, editor_open_block, editor_save, editor_save_as, editor_clip,
+    editor_clip_prefix, editor_export_svg,
+    editor_export_html, editor_paste, editor_put, editor_quit, editor_undo, editor_redo,
+    editor_undo_older, editor_undo_newer, editor_undo_list,
     editor_trim_margins, editor_tool, modify_opts, editor_help,
     new_scrollview
 };
@@ -44,7 +49,6 @@ use crate::tools::{
     lines::{arrowtool::ArrowTool, boxtool::BoxTool, linetool::LineTool},
     texttool::TextTool,
     selecttool::SelectTool,
-    PathMode::{Snap90, Routed}
 };
 
 fn main() -> Result<(), Box<dyn Error>> {
@@ -61,6 +65,14 @@ fn main() -> Result<(), Box<dyn Error>> {
     opts.resolve_config();
     debug!("{:?}", opts);
 
+    if let Some(cmd) = opts.command.take() {
+        if let Err(e) = cli::run(&cmd, &opts) {
+            eprintln!("unikode: {}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
     let editor = EditorView::new(Editor::open(opts.clone())?);
     let mut siv = cursive::crossterm();
     let mut theme = siv.current_theme().clone();
@@ -102,10 +114,13 @@ fn main() -> Result<(), Box<dyn Error>> {
             Tree::new()
                 .leaf(format!("({}) New", KEY_NEW), editor_new)
                 .leaf(format!("({}) Open", KEY_OPEN), editor_open)
+                .leaf(format!("({}) Open Block", KEY_OPEN_BLOCK), editor_open_block)
                 .leaf(format!("({}) Save", KEY_SAVE), editor_save)
                 .leaf(format!("({}) Save As", KEY_SAVE_AS), editor_save_as)
                 .leaf(format!("({}) Clip", KEY_CLIP), editor_clip)
                 .leaf(format!("({}) Clip Prefix", KEY_CLIP_PREFIX), editor_clip_prefix)
+                .leaf(format!("({}) Export SVG", KEY_EXPORT_SVG), editor_export_svg)
+                .leaf(format!("({}) Export HTML", KEY_EXPORT_HTML), editor_export_html)
                 .delimiter()
                 .leaf(format!("({}) Debug", KEY_DEBUG), Cursive::toggle_debug_console)
                 .leaf(format!("({}) Quit", KEY_QUIT), editor_quit),
@@ -115,6 +130,11 @@ fn main() -> Result<(), Box<dyn Error>> {
             Tree::new()
                 .leaf(format!("({}) Undo", KEY_UNDO), editor_undo)
                 .leaf("(Ctrl+r) Redo", editor_redo)
+                .leaf("(Ctrl+v) Paste", editor_paste)
+                .leaf(format!("({}) Put", KEY_PUT), editor_put)
+                .leaf(format!("({}) Older State", KEY_UNDO_OLDER), editor_undo_older)
+                .leaf(format!("({}) Newer State", KEY_UNDO_NEWER), editor_undo_newer)
+                .leaf(format!("({}) Undo Tree", KEY_UNDO_LIST), editor_undo_list)
                 .leaf(format!("({}) Trim Margins", KEY_TRIM_MARGINS), editor_trim_margins),
         )
         .add_leaf("Help", editor_help);
@@ -124,16 +144,24 @@ fn main() -> Result<(), Box<dyn Error>> {
     // File
     siv.add_global_callback(KEY_NEW, editor_new);
     siv.add_global_callback(KEY_OPEN, editor_open);
+    siv.add_global_callback(KEY_OPEN_BLOCK, editor_open_block);
     siv.add_global_callback(KEY_SAVE, editor_save);
     siv.add_global_callback(KEY_SAVE_AS, editor_save_as);
     siv.add_global_callback(KEY_CLIP, editor_clip);
     siv.add_global_callback(KEY_CLIP_PREFIX, editor_clip_prefix);
+    siv.add_global_callback(KEY_EXPORT_SVG, editor_export_svg);
+    siv.add_global_callback(KEY_EXPORT_HTML, editor_export_html);
     siv.add_global_callback(KEY_DEBUG, Cursive::toggle_debug_console);
     siv.add_global_callback(KEY_QUIT, editor_quit);
 
     // Edit
     siv.add_global_callback(KEY_UNDO, editor_undo);
     siv.add_global_callback(Event::CtrlChar('r'), editor_redo);
+    siv.add_global_callback(Event::CtrlChar('v'), editor_paste);
+    siv.add_global_callback(KEY_PUT, editor_put);
+    siv.add_global_callback(KEY_UNDO_OLDER, editor_undo_older);
+    siv.add_global_callback(KEY_UNDO_NEWER, editor_undo_newer);
+    siv.add_global_callback(KEY_UNDO_LIST, editor_undo_list);
     siv.add_global_callback(KEY_TRIM_MARGINS, editor_trim_margins);
 
     // Tools
@@ -1,19 +1,22 @@
 pub mod lines;
 pub mod erasetool;
 pub mod movetool;
+pub mod resizetool;
 pub mod texttool;
 pub mod selecttool;
+pub mod transform;
 
 use cursive::{
     event::{Event, EventResult},
     Rect, Vec2,
 };
-use std::fmt;
+use std::{fmt, mem, thread, time::{Duration, Instant}};
 
 use crate::config::{Options, Symbols};
+use crate::constants::CONSUMED;
 use crate::editor::{
     buffer::Buffer,
-    cell::{Cell, Char},
+    cell::{Cell, Char, Glyph},
     scroll::EditorCtx
 };
 
@@ -54,30 +57,60 @@ macro_rules! mouse_drag {
 pub(crate) use mouse_drag;
 
 /// Provides an implementation of `Tool::on_event` for tools that contain a `src` and
-/// `dst` field of type `Option<Vec2>`. The implementation performs basic left mouse
-/// drag handling, calling the argument closure when relevant events occur.
+/// `dst` field of type `Option<Vec2>`, along with a `bend` and a `throttle`. The
+/// implementation performs basic left mouse drag handling, calling the argument closure
+/// when relevant events occur.
 macro_rules! fn_on_event_drag {
     ($render:expr) => {
         fn on_event(&mut self, ctx: &mut EditorCtx<'_>, event: &Event) -> Option<EventResult> {
-            let (pos, event) = mouse_drag!(ctx, event);
+            // flip the bend of the line being dragged
+            if let (Event::Char($crate::constants::KEY_FLIP_BEND), Some(src), Some(dst)) = (event, self.src, self.dst) {
+                let mut editor = ctx.0.get_inner_mut().write();
+                editor.flip_bend(src, dst);
+                self.bend = editor.bend(src, dst);
+                drop(editor);
+                ctx.preview(|buf| $render(self, buf));
+                return CONSUMED;
+            }
+
+            let (pos, event) = match (event, self.dst) {
+                // a preview skipped while dragging is drawn once there is time for it
+                (Event::Refresh, Some(dst)) if self.throttle.take_pending() => (dst, Hold(Left)),
+                _ => mouse_drag!(ctx, event),
+            };
 
             match event {
                 Press(Left) => {
                     self.src = Some(pos);
                     self.dst = Some(pos);
+                    self.bend = ctx.0.get_inner_mut().write().bend(pos, pos);
+                    self.throttle = Throttle::default();
                     ctx.preview(|buf| $render(self, buf));
                 }
 
                 Hold(Left) => {
                     self.dst = Some(pos);
+                    if let Some(src) = self.src {
+                        self.bend = ctx.0.get_inner_mut().write().bend(src, pos);
+                    }
+                    if !self.throttle.ready() {
+                        return self.throttle.skip();
+                    }
+                    let start = std::time::Instant::now();
                     ctx.preview(|buf| $render(self, buf));
+                    self.throttle.wait(start.elapsed());
                 }
 
                 Release(Left) => {
                     self.dst = Some(pos);
-                    ctx.clobber(|buf| $render(self, buf));
+                    if let Some(src) = self.src {
+                        self.bend = ctx.0.get_inner_mut().write().bend(src, pos);
+                    }
+                    ctx.clobber(&self.to_string(), |buf| $render(self, buf));
+                    ctx.0.get_inner_mut().write().forget_bend();
                     self.src = None;
                     self.dst = None;
+                    self.throttle = Throttle::default();
                 }
 
                 _ => return None,
@@ -89,6 +122,52 @@ macro_rules! fn_on_event_drag {
 }
 pub(crate) use fn_on_event_drag;
 
+/// Skips drag previews that arrive faster than they can be drawn, so that slow
+/// renders such as routed lines do not fall behind the mouse. The last skipped preview
+/// is drawn once the wait is over, even if the mouse has stopped moving by then.
+#[derive(Copy, Clone, Debug, Default)]
+pub(crate) struct Throttle {
+    next: Option<Instant>,
+    /// Whether a preview was skipped, and a refresh to draw it is on its way.
+    pending: bool,
+}
+
+impl Throttle {
+    /// Returns `true` if the next preview can be drawn.
+    pub(crate) fn ready(&self) -> bool {
+        self.next.is_none_or(|next| Instant::now() >= next)
+    }
+
+    /// Hold off further previews for as long as the last one took to draw.
+    pub(crate) fn wait(&mut self, took: Duration) {
+        self.next = Some(Instant::now() + took);
+    }
+
+    /// Skip a preview, asking for an `Event::Refresh` once the wait is over so that it
+    /// can be drawn then.
+    pub(crate) fn skip(&mut self) -> Option<EventResult> {
+        if mem::replace(&mut self.pending, true) {
+            return CONSUMED;
+        }
+
+        let delay = self.next.map_or(Duration::ZERO, |next| next.saturating_duration_since(Instant::now()));
+        Some(EventResult::with_cb(move |siv| {
+            let sink = siv.cb_sink().clone();
+            thread::spawn(move || {
+                thread::sleep(delay);
+                let _ = sink.send(Box::new(|siv| {
+                    siv.on_event(Event::Refresh);
+                }));
+            });
+        }))
+    }
+
+    /// Returns `true` if a preview was skipped since the last call.
+    pub(crate) fn take_pending(&mut self) -> bool {
+        mem::take(&mut self.pending)
+    }
+}
+
 #[macro_export]
  macro_rules! simple_display {
     ($type:ty, $fstr:expr) => {
@@ -111,26 +190,64 @@ pub(crate) trait Tool: fmt::Display {
     }
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, Default)]
 pub(crate) enum PathMode {
+    #[default]
     Snap90,
+    /// A straight run, then a diagonal at 45°.
+    Snap45,
+    /// A straight line at any angle.
+    Free,
     Routed,
+    /// Routed around obstacles with horizontal and vertical lines only.
+    Orthogonal,
 }
 
-impl Default for PathMode {
-    fn default() -> Self {
-        Self::Snap90
+/// Which way a Snap90 line leaves its start.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) enum Bend {
+    /// Horizontally, then vertically.
+    #[default]
+    Horizontal,
+    /// Vertically, then horizontally.
+    Vertical,
+}
+
+impl Bend {
+    /// Returns the bend for a line whose end first moved from `src` to `dst`, so that it
+    /// leaves in the direction it was drawn.
+    pub(crate) fn towards(src: Vec2, dst: Vec2) -> Self {
+        let (dx, dy) = (dst.x.abs_diff(src.x), dst.y.abs_diff(src.y));
+
+        if dx >= dy { Bend::Horizontal } else { Bend::Vertical }
+    }
+
+    pub(crate) fn flip(self) -> Self {
+        match self {
+            Bend::Horizontal => Bend::Vertical,
+            Bend::Vertical => Bend::Horizontal,
+        }
     }
 }
 
+/// Returns the visible cells within the rect with corners `cs`, including the other
+/// half of any double width cell that the rect's left or right edge cuts through.
 pub(crate) fn visible_cells<'a>(buf: &'a Buffer, cs: (Vec2, Vec2), symbols: &'a Symbols) -> impl Iterator<Item = Cell> + 'a {
     let area = Rect::from_corners(cs.0, cs.1);
+    let outer = Rect::from_corners(area.top_left().map_x(|x| x.saturating_sub(1)), area.bottom_right().map_x(|x| x + 1));
+
+    let split = move |cell: &Cell| {
+        let right = cell.pos.map_x(|x| x + 1);
+        (right.x == area.left() && buf.glyph_at(right) == Glyph::Wide)
+            || (cell.pos.x == area.right() + 1 && cell.glyph == Glyph::Wide)
+    };
 
-    buf.iter_within(area.top_left(), area.size(), symbols)
+    buf.iter_within(outer.top_left(), outer.size(), symbols)
         .filter_map(|c| match c {
             Char::Clean(cell) => Some(cell),
             Char::Dirty(cell) => Some(cell),
             _ => None,
         })
+        .filter(move |cell| area.contains(cell.pos) || split(cell))
         .filter(|cell| !cell.is_whitespace())
 }
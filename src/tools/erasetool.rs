@@ -1,8 +1,10 @@
 use cursive::Vec2;
+use std::collections::HashSet;
 
 use crate::editor::buffer::Buffer;
-use crate::constants::SP;
+use crate::constants::{SP, S_N, S_E, S_S, S_W};
 use crate::config::Symbols;
+use crate::utils::junctions::{fixup_point, is_joinable};
 use super::visible_cells;
 
 pub fn erase_on_buffer(buf: &mut Buffer, src: Vec2, dst: Vec2, symbols: &Symbols) {
@@ -14,3 +16,31 @@ pub fn erase_on_buffer(buf: &mut Buffer, src: Vec2, dst: Vec2, symbols: &Symbols
     
     buf.set_cursor(dst);
 }
+
+/// Erase `cells`, then repair the junctions next to `joins` that lines or boxes were
+/// attached to.
+pub fn erase_shape_on_buffer(buf: &mut Buffer, cells: &[Vec2], joins: &[Vec2], symbols: &Symbols) {
+    for &pos in cells {
+        buf.setv(true, pos, SP, symbols);
+    }
+
+    let erased: HashSet<_> = cells.iter().copied().collect();
+    let around: HashSet<_> = joins
+        .iter()
+        .flat_map(|&pos| [S_N, S_E, S_S, S_W].into_iter().filter_map(move |dir| pos.checked_add(dir)))
+        .filter(|pos| !erased.contains(pos))
+        .collect();
+
+    let mut change_set = Vec::new();
+    for pos in around {
+        let new_char = fixup_point(pos, buf, symbols);
+
+        if is_joinable(new_char, symbols) && new_char != buf.get_char_at(pos) {
+            change_set.push((pos, new_char));
+        }
+    }
+
+    for (pos, c) in change_set {
+        buf.setv(true, pos, c, symbols);
+    }
+}
@@ -1,43 +1,51 @@
 pub mod arrowtool;
 pub mod boxtool;
+pub mod connector;
 pub mod linetool;
+pub mod polylinetool;
 
 use line_drawing::Bresenham;
-use cursive::{Vec2, XY};
+use cursive::{Rect, Vec2, XY};
 use pathfinding::directed::astar::astar;
-use std::cmp::min;
+use std::{cell::Cell, cmp::min};
 
 use crate::editor::buffer::Buffer;
 use crate::utils::ordfloat::OrdFloat;
 use crate::config::Symbols;
 use crate::constants::{
-    D, D2
+    D, D2, BEND, ROUTE_BUDGET, ROUTE_MARGIN, S_N, S_E, S_S, S_W
 };
 
 use crate::utils::junctions::*;
 
-/// Draw the shortest path from `src` to `dst`. Returns the penultimate point
-/// along that path.
-fn draw_path(buf: &mut Buffer, src: Vec2, dst: Vec2, symbols: &Symbols) -> Vec2 {
+use super::{Bend, PathMode};
+
+/// Draw the shortest path from `src` to `dst`. Returns the points along that path.
+///
+/// Falls back to a right angled line if the search gives up.
+fn draw_path(buf: &mut Buffer, src: Vec2, dst: Vec2, symbols: &Symbols) -> Vec<Vec2> {
+    let bounds = route_bounds(buf, src, dst);
+    let budget = Cell::new(ROUTE_BUDGET);
+
     let path_points = astar(
         &src.pair(),
-        |&pos| buf.neighbors(pos),
+        |&pos| match spend(&budget) {
+            true => buf.neighbors(pos).into_iter().filter(|&(p, _)| bounds.contains(p.into())).collect(),
+            false => vec![],
+        },
         |&pos| heuristic(pos.into(), dst),
         |&pos| pos == dst.pair(),
-    )
-    .map(|(points, _)| points)
-    .unwrap()
-    .into_iter()
-    .map(Vec2::from)
-    .collect::<Vec<_>>();
+    );
+
+    let path_points = match path_points {
+        Some((points, _)) => points.into_iter().map(Vec2::from).collect::<Vec<_>>(),
+        None => return draw_fallback(buf, src, dst, symbols),
+    };
 
     let decide = |i: usize, last: Vec2, pos: Vec2| -> char {
         match line_slope(last, pos).pair() {
             _ if i == 0 => symbols.plus,
-            (0, _) => symbols.vline,
-            (_, 0) => symbols.hline,
-            (x, y) if (x > 0) == (y > 0) => symbols.gaid2,
-            _ => symbols.diag2,
+            slope => line_char(slope, symbols),
         }
     };
 
@@ -60,7 +68,117 @@ fn draw_path(buf: &mut Buffer, src: Vec2, dst: Vec2, symbols: &Symbols) -> Vec2
 
     fixup(buf, &path_points, false, symbols);
 
-    last
+    path_points
+}
+
+/// Draw the shortest path from `src` to `dst` that only moves horizontally and
+/// vertically, preferring as few bends as possible. Returns the points along that path.
+fn draw_route(buf: &mut Buffer, src: Vec2, dst: Vec2, symbols: &Symbols) -> Vec<Vec2> {
+    let dirs = [S_N, S_E, S_S, S_W];
+    let bounds = route_bounds(buf, src, dst);
+    let budget = Cell::new(ROUTE_BUDGET);
+
+    // each step remembers the direction it was taken in, so bends can be costed
+    let path_points = astar(
+        &(src, None),
+        |&(pos, dir): &(Vec2, Option<(isize, isize)>)| {
+            if !spend(&budget) {
+                return vec![];
+            }
+
+            dirs.into_iter()
+                .filter_map(|d| pos.checked_add(d).map(|next| (next, d)))
+                .filter(|&(next, _)| bounds.contains(next))
+                .map(|(next, d)| {
+                    let spot = buf.visible(next) as u8 as f64 * 64.0;
+                    let bend = if dir.is_some_and(|dir| dir != d) { BEND } else { 0.0 };
+                    ((next, Some(d)), OrdFloat(spot + bend + D))
+                })
+                .collect::<Vec<_>>()
+        },
+        |&(pos, _)| manhattan(pos, dst),
+        |&(pos, _)| pos == dst,
+    );
+
+    let path_points = match path_points {
+        Some((points, _)) => points.into_iter().map(|(pos, _)| pos).collect::<Vec<_>>(),
+        None => return draw_fallback(buf, src, dst, symbols),
+    };
+
+    // join each point to the ones before and after it, so bends get corners
+    for (i, &pos) in path_points.iter().enumerate() {
+        let joined = |dir| {
+            let near = |other: Option<&Vec2>| other.is_some_and(|&o| pos.checked_add(dir) == Some(o));
+            near(i.checked_sub(1).and_then(|i| path_points.get(i))) || near(path_points.get(i + 1))
+        };
+
+        let c = get_smart_char(joined(S_N), joined(S_S), joined(S_W), joined(S_E), symbols, symbols.plus);
+        buf.setv(false, pos, c, symbols);
+    }
+    buf.set_cursor(dst);
+
+    fixup(buf, &path_points, false, symbols);
+
+    path_points
+}
+
+/// Draw a right angled line from `src` to `dst`, for when no route is found. Returns
+/// the points along it.
+fn draw_fallback(buf: &mut Buffer, src: Vec2, dst: Vec2, symbols: &Symbols) -> Vec<Vec2> {
+    let mid = snap90(src, dst, Bend::towards(src, dst));
+
+    let mut points = draw_line(buf, src, mid, symbols);
+    points.extend(draw_line(buf, mid, dst, symbols));
+    fixup(buf, &points, false, symbols);
+
+    points
+}
+
+/// Draw one segment of a polyline from `src` to `dst`. Returns the points along it.
+fn draw_segment(buf: &mut Buffer, src: Vec2, dst: Vec2, path_mode: PathMode, symbols: &Symbols) -> Vec<Vec2> {
+    match path_mode {
+        PathMode::Routed => draw_path(buf, src, dst, symbols),
+        PathMode::Orthogonal => draw_route(buf, src, dst, symbols),
+        _ => draw_snapped(buf, src, dst, path_mode, Bend::towards(src, dst), symbols),
+    }
+}
+
+/// Draw a line from `src` to `dst` in one of the path modes that is not routed, bending
+/// as `bend` in Snap90 mode. Returns the points along it.
+fn draw_snapped(buf: &mut Buffer, src: Vec2, dst: Vec2, path_mode: PathMode, bend: Bend, symbols: &Symbols) -> Vec<Vec2> {
+    let mid = match path_mode {
+        PathMode::Free => return draw_free(buf, src, dst, symbols),
+        PathMode::Snap45 => snap45(src, dst),
+        _ => snap90(src, dst, bend),
+    };
+
+    let mut points = draw_line(buf, src, mid, symbols);
+    points.extend(draw_line(buf, mid, dst, symbols));
+
+    points
+}
+
+/// Returns the last of `points` before `dst`, which an arrow tip at `dst` points away
+/// from.
+fn before(points: &[Vec2], dst: Vec2) -> Vec2 {
+    points.iter().rev().find(|&&p| p != dst).copied().unwrap_or(dst)
+}
+
+/// Returns the area to search for routes from `src` to `dst` within: the buffer and
+/// both endpoints, with a margin to go around anything on the edges.
+fn route_bounds(buf: &Buffer, src: Vec2, dst: Vec2) -> Rect {
+    let far = Vec2::max(Vec2::max(buf.bounds(), src), dst);
+
+    Rect::from_corners(Vec2::zero(), far + (ROUTE_MARGIN, ROUTE_MARGIN))
+}
+
+/// Counts off one step of a route search from `budget`. Returns `false` once it has
+/// run out, so that searches through large diagrams give up rather than stall.
+fn spend(budget: &Cell<usize>) -> bool {
+    let left = budget.get();
+    budget.set(left.saturating_sub(1));
+
+    left > 0
 }
 
 /// Draw a line from `src` to `dst`.
@@ -73,10 +191,7 @@ fn draw_line(buf: &mut Buffer, src: Vec2, dst: Vec2, symbols: &Symbols) -> Vec<V
         let pos = Vec2::new(s.0 as usize, s.1 as usize);
         let c = match line_slope(src, dst).pair() {
             _ if i == 0 => symbols.plus,
-            (0, _) => symbols.vline,
-            (_, 0) => symbols.hline,
-            (x, y) if (x > 0) == (y > 0) => symbols.gaid2,
-            _ => symbols.diag2,
+            slope => line_char(slope, symbols),
         };
 
         buf.set(false, pos.x, pos.y, c, symbols);
@@ -90,6 +205,25 @@ fn draw_line(buf: &mut Buffer, src: Vec2, dst: Vec2, symbols: &Symbols) -> Vec<V
     touched
 }
 
+/// Draw a line from `src` to `dst` at any angle. Each cell is drawn with the glyph for
+/// the step taken out of it, so the line looks the same as in the other path modes.
+fn draw_free(buf: &mut Buffer, src: Vec2, dst: Vec2, symbols: &Symbols) -> Vec<Vec2> {
+    let mut touched = Vec::new();
+    for (s, e) in Bresenham::new(src.signed().pair(), dst.signed().pair()).steps() {
+        let pos = Vec2::new(s.0 as usize, s.1 as usize);
+        let c = line_char((e.0 - s.0, e.1 - s.1), symbols);
+
+        buf.setv(false, pos, c, symbols);
+        touched.push(pos);
+    }
+
+    buf.setv(false, dst, symbols.plus, symbols);
+    touched.push(dst);
+    buf.set_cursor(dst);
+
+    touched
+}
+
 pub(crate) fn fixup(buf: &mut Buffer, points: &[Vec2], is_arrow: bool, symbols: &Symbols) {
     if points.is_empty() { return; }
     
@@ -112,6 +246,61 @@ pub(crate) fn fixup(buf: &mut Buffer, points: &[Vec2], is_arrow: bool, symbols:
     }
 }
 
+/// Join the diagonal runs among `points` up with the rest of the line. A `plus` left at
+/// the end of a run, and the last cell of a straight run that turns onto one, are
+/// replaced with the diagonal they continue, so the straight run ends a cell short and
+/// the diagonal picks up where it left off. Cells that join anything else are kept.
+pub(crate) fn fixup_diagonals(buf: &mut Buffer, points: &[Vec2], symbols: &Symbols) {
+    let falling = [symbols.gaid, symbols.gaid2];
+    let rising = [symbols.diag, symbols.diag2];
+    let straight = [symbols.plus, symbols.hline, symbols.vline];
+
+    let mut change_set = Vec::new();
+    for &p in points.iter().filter(|&&p| straight.contains(&buf.get_char_at(p))) {
+        let c = buf.get_char_at(p);
+        let joined: Vec<_> = [S_N, S_E, S_S, S_W]
+            .into_iter()
+            .filter(|&dir| p.checked_add(dir).is_some_and(|next| joins(c, buf.get_char_at(next), dir, symbols)))
+            .collect();
+
+        // a turn is joined to the straight run it ends, and the diagonal leaves on the
+        // far side of it
+        let turn = match joined.as_slice() {
+            [] if c == symbols.plus => None,
+            [dir] if p.checked_add(*dir).is_some_and(|next| points.contains(&next)) => Some(*dir),
+            _ => continue,
+        };
+
+        let continues = [((1, 1), falling), ((-1, -1), falling), ((1, -1), rising), ((-1, 1), rising)]
+            .into_iter()
+            .filter(|(dir, _)| turn.is_none_or(|t: (isize, isize)| dir.0 * t.0 + dir.1 * t.1 < 0))
+            .filter_map(|(dir, glyphs)| {
+                let next = p.checked_add(dir).filter(|next| points.contains(next))?;
+                let c = buf.get_char_at(next);
+                glyphs.contains(&c).then_some(c)
+            })
+            .next();
+
+        if let Some(c) = continues {
+            change_set.push((p, c));
+        }
+    }
+
+    for (pos, c) in change_set {
+        buf.setv(true, pos, c, symbols);
+    }
+}
+
+/// Returns whether `c` and its neighbour `next` in direction `dir` are joined up.
+fn joins(c: char, next: char, dir: (isize, isize), s: &Symbols) -> bool {
+    match dir {
+        S_N => connects_up(c, s) && connects_down(next, s),
+        S_E => connects_right(c, s) && connects_left(next, s),
+        S_S => connects_down(c, s) && connects_up(next, s),
+        _ => connects_left(c, s) && connects_right(next, s),
+    }
+}
+
 fn snap45(src: Vec2, dst: Vec2) -> Vec2 {
     let delta = min(diff(src.y, dst.y), diff(src.x, dst.x));
 
@@ -129,11 +318,22 @@ fn snap45(src: Vec2, dst: Vec2) -> Vec2 {
     }
 }
 
-fn snap90(buf: &mut Buffer, src: Vec2, dst: Vec2, symbols: &Symbols) -> Vec2 {
-    if buf.getv(dst) == Some(symbols.hline) {
-        Vec2::new(dst.x, src.y)
-    } else {
-        Vec2::new(src.x, dst.y)
+/// Returns the corner of a right angled line from `src` to `dst` that bends as `bend`.
+fn snap90(src: Vec2, dst: Vec2, bend: Bend) -> Vec2 {
+    match bend {
+        Bend::Horizontal => Vec2::new(dst.x, src.y),
+        Bend::Vertical => Vec2::new(src.x, dst.y),
+    }
+}
+
+/// Returns the glyph for a line heading along `slope`: `hline` or `vline` when it is
+/// straight, and `diag2` or `gaid2` otherwise.
+fn line_char(slope: (isize, isize), symbols: &Symbols) -> char {
+    match slope {
+        (0, _) => symbols.vline,
+        (_, 0) => symbols.hline,
+        (x, y) if (x > 0) == (y > 0) => symbols.gaid2,
+        _ => symbols.diag2,
     }
 }
 
@@ -164,6 +364,16 @@ fn diff(a: usize, b: usize) -> usize {
     (a as isize - b as isize).abs().unsigned_abs()
 }
 
+/// Returns a distance heuristic between `pos` and `dst` for paths without diagonals.
+fn manhattan(pos: Vec2, dst: Vec2) -> OrdFloat {
+    let dist = D * (diff(pos.x, dst.x) + diff(pos.y, dst.y)) as f64;
+
+    // prefer to expand paths close to dst, as in `heuristic`
+    const P: f64 = 1.0 + (1.0 / 1000.0);
+
+    OrdFloat(dist * P)
+}
+
 /// Returns a distance heuristic between `pos` and `dst`.
 fn heuristic(pos: Vec2, dst: Vec2) -> OrdFloat {
     // base is diagonal distance:
@@ -183,3 +393,116 @@ fn heuristic(pos: Vec2, dst: Vec2) -> OrdFloat {
 
     OrdFloat(dist * P)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn buffer(text: &str) -> Buffer {
+        Buffer::read_from(text.as_bytes(), 8).unwrap()
+    }
+
+    /// Returns the text of a line drawn from `src` to `dst` on an empty buffer.
+    fn drawn(path_mode: PathMode, src: Vec2, dst: Vec2) -> String {
+        let mut buf = buffer("");
+        linetool::draw_line_on_buffer(&mut buf, src, dst, path_mode, Bend::default(), &Symbols::default());
+        buf.flush_edits();
+        buf.iter("").collect()
+    }
+
+    /// Returns `true` if each of `points` is next to the one before it.
+    fn connected(points: &[Vec2]) -> bool {
+        points.windows(2).all(|w| diff(w[0].x, w[1].x) + diff(w[0].y, w[1].y) == 1)
+    }
+
+    #[test]
+    fn route_bounds_cover_buffer_and_endpoints() {
+        let buf = buffer("abc\nde\n");
+        let margin = (ROUTE_MARGIN, ROUTE_MARGIN);
+
+        let bounds = route_bounds(&buf, Vec2::new(1, 0), Vec2::new(0, 1));
+        assert_eq!(bounds, Rect::from_corners((0, 0), buf.bounds() + margin));
+
+        let bounds = route_bounds(&buf, Vec2::new(40, 0), Vec2::new(2, 30));
+        assert_eq!(bounds, Rect::from_corners((0, 0), Vec2::new(40, 30) + margin));
+    }
+
+    #[test]
+    fn spend_runs_out() {
+        let budget = Cell::new(2);
+        assert!(spend(&budget));
+        assert!(spend(&budget));
+        assert!(!spend(&budget));
+        assert!(!spend(&budget));
+        assert_eq!(budget.get(), 0);
+    }
+
+    #[test]
+    fn routes_go_around_text() {
+        let mut buf = buffer("\n\n  #\n");
+        let (src, dst) = (Vec2::new(0, 2), Vec2::new(4, 2));
+
+        let points = draw_route(&mut buf, src, dst, &Symbols::default());
+        assert_eq!((points.first(), points.last()), (Some(&src), Some(&dst)));
+        assert!(connected(&points));
+        assert!(!points.contains(&Vec2::new(2, 2)));
+    }
+
+    #[test]
+    fn fallback_bends_once() {
+        let mut buf = buffer("");
+        let (src, dst) = (Vec2::new(0, 0), Vec2::new(3, 2));
+
+        let points = draw_fallback(&mut buf, src, dst, &Symbols::default());
+        buf.flush_edits();
+        assert_eq!(buf.iter("").collect::<String>(), "───┐\n   │\n   │\n");
+        assert_eq!(points.last(), Some(&dst));
+    }
+
+    #[test]
+    fn diagonal_ends_continue_the_diagonal() {
+        assert_eq!(drawn(PathMode::Free, Vec2::new(0, 0), Vec2::new(2, 2)), "╲\n ╲\n  ╲\n");
+        assert_eq!(drawn(PathMode::Free, Vec2::new(0, 2), Vec2::new(2, 0)), "  ╱\n ╱\n╱\n");
+        for dst in [Vec2::new(0, 0), Vec2::new(4, 0), Vec2::new(0, 4), Vec2::new(4, 4)] {
+            let src = Vec2::new(2, 2);
+            assert_eq!(drawn(PathMode::Free, src, dst), drawn(PathMode::Snap45, src, dst));
+        }
+        assert_eq!(drawn(PathMode::Snap45, Vec2::new(0, 0), Vec2::new(4, 2)), "──╲\n   ╲\n    ╲\n");
+    }
+
+    #[test]
+    fn only_ends_of_the_line_are_fixed() {
+        let symbols = Symbols::default();
+        let mut buf = buffer("+\n \\\n  +\n   +\n");
+        let points = [Vec2::new(1, 1), Vec2::new(2, 2)];
+
+        fixup_diagonals(&mut buf, &points, &symbols);
+        buf.flush_edits();
+        assert_eq!(buf.iter("").collect::<String>(), "+\n \\\n  \\\n   +\n");
+    }
+
+    #[test]
+    fn straight_runs_turn_onto_diagonals() {
+        assert_eq!(drawn(PathMode::Snap45, Vec2::new(0, 4), Vec2::new(2, 0)), "  ╱\n ╱\n╱\n│\n│\n");
+        assert_eq!(drawn(PathMode::Snap45, Vec2::new(4, 0), Vec2::new(0, 2)), "  ╱──\n ╱\n╱\n");
+
+        let mut buf = buffer("");
+        let points = [Vec2::new(0, 0), Vec2::new(2, 2), Vec2::new(5, 2)];
+        polylinetool::draw_polyline_on_buffer(&mut buf, &points, false, PathMode::Snap45, &Symbols::default());
+        buf.flush_edits();
+        assert_eq!(buf.iter("").collect::<String>(), "╲\n ╲\n  ╲───\n");
+    }
+
+    #[test]
+    fn free_steps_are_left_alone() {
+        assert_eq!(drawn(PathMode::Free, Vec2::new(0, 0), Vec2::new(5, 2)), "──╲\n   ─╲\n     ╲\n");
+    }
+
+    #[test]
+    fn turns_joined_to_other_lines_are_kept() {
+        let mut buf = buffer("\n  │\n");
+        linetool::draw_line_on_buffer(&mut buf, Vec2::new(0, 0), Vec2::new(4, 2), PathMode::Snap45, Bend::default(), &Symbols::default());
+        buf.flush_edits();
+        assert_eq!(buf.iter("").collect::<String>(), "──┐\n  │╲\n    ╲\n");
+    }
+}
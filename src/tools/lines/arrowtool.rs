@@ -16,10 +16,10 @@ use crate::constants::{
 };
 
 use super::super::{
-    PathMode, Tool, fn_on_event_drag, option, mouse_drag
+    PathMode, Bend, Throttle, Tool, fn_on_event_drag, option, mouse_drag
 };
 use super::{
-    draw_path, draw_line, line_slope, snap45, snap90, fixup
+    before, draw_path, draw_route, draw_snapped, line_slope, fixup, fixup_diagonals
 };
 
 #[derive(Clone, Default)]
@@ -27,6 +27,8 @@ pub(crate) struct ArrowTool {
     src: Option<Vec2>,
     dst: Option<Vec2>,
     path_mode: PathMode,
+    bend: Bend,
+    throttle: Throttle,
     symbols: Symbols,
 }
 
@@ -45,35 +47,26 @@ impl Tool for ArrowTool {
 
     fn_on_event_drag!(|t: &Self, buf: &mut Buffer| {
         let (src, dst) = option!(t.src, t.dst);
-        draw_arrow_on_buffer(buf, src, dst, t.path_mode, &t.symbols);
+        draw_arrow_on_buffer(buf, src, dst, t.path_mode, t.bend, &t.symbols);
     });
 }
 
-pub fn draw_arrow_on_buffer(buf: &mut Buffer, src: Vec2, dst: Vec2, path_mode: PathMode, symbols: &Symbols) {
-    if let PathMode::Routed = path_mode {
-        let last = draw_path(buf, src, dst, symbols);
-        draw_arrow_tip(buf, last, dst, symbols);
-        return;
-    }
-
-    let mid = match path_mode {
-        PathMode::Snap90 => snap90(buf, src, dst, symbols),
-        _ => snap45(src, dst),
+pub fn draw_arrow_on_buffer(buf: &mut Buffer, src: Vec2, dst: Vec2, path_mode: PathMode, bend: Bend, symbols: &Symbols) {
+    let points = match path_mode {
+        PathMode::Routed => draw_path(buf, src, dst, symbols),
+        PathMode::Orthogonal => draw_route(buf, src, dst, symbols),
+        _ => {
+            let points = draw_snapped(buf, src, dst, path_mode, bend, symbols);
+            fixup(buf, &points, true, symbols);
+            fixup_diagonals(buf, &points, symbols);
+            points
+        }
     };
 
-    if mid != dst {
-        let mut points = draw_line(buf, src, mid, symbols);
-        points.extend(draw_line(buf, mid, dst, symbols));
-        fixup(buf, &points, true, symbols);
-        draw_arrow_tip(buf, mid, dst, symbols);
-    } else {
-        let points = draw_line(buf, src, dst, symbols);
-        fixup(buf, &points, true, symbols);
-        draw_arrow_tip(buf, src, dst, symbols);
-    }
+    draw_arrow_tip(buf, before(&points, dst), dst, symbols);
 }
 
-fn draw_arrow_tip(buf: &mut Buffer, src: Vec2, dst: Vec2, symbols: &Symbols) {
+pub(super) fn draw_arrow_tip(buf: &mut Buffer, src: Vec2, dst: Vec2, symbols: &Symbols) {
     let dec = |v: usize| v - 1;
     let inc = |v: usize| v + 1;
 
@@ -19,7 +19,7 @@ use crate::{
     config::{Options, Symbols}
 };
 
-use super::super::{Tool, simple_display, fn_on_event_drag, option, mouse_drag};
+use super::super::{Tool, simple_display, mouse_drag};
 
 #[derive(Clone, Default)]
 pub(crate) struct BoxTool {
@@ -54,7 +54,7 @@ impl Tool for BoxTool {
 
             Release(Left) => {
                 self.dst = Some(pos);
-                ctx.clobber(|buf| {
+                ctx.clobber("Box", |buf| {
                     if let Some(src) = self.src {
                         draw_box_on_buffer(buf, src, pos, &self.symbols);
                     }
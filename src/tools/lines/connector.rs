@@ -0,0 +1,182 @@
+use cursive::{Rect, Vec2, XY};
+use std::collections::HashSet;
+
+use crate::editor::buffer::Buffer;
+use crate::config::Symbols;
+use crate::constants::{SP, S_N, S_E, S_S, S_W};
+use crate::utils::junctions::*;
+
+use super::super::PathMode;
+use super::{arrowtool::draw_arrow_tip, before, draw_line, draw_path, draw_route, fixup};
+
+/// A line or arrow leading away from the edge of a selection.
+#[derive(Clone, Debug)]
+pub(crate) struct Connector {
+    /// The cell just outside the selection where the line starts.
+    start: Vec2,
+    /// Direction from the selection edge towards `start`.
+    dir: (isize, isize),
+    /// The far end of the line.
+    end: Vec2,
+    /// Direction from `end` back along the line.
+    end_dir: (isize, isize),
+    /// Cells that only belong to this line. A junction at `end` is shared with other
+    /// lines, so it is left out.
+    cells: Vec<Vec2>,
+    tip_start: bool,
+    tip_end: bool,
+}
+
+/// Returns the lines and arrows attached to the edges of `selection`.
+pub(crate) fn find_connectors(buf: &Buffer, selection: Rect, symbols: &Symbols) -> Vec<Connector> {
+    let (left, top) = selection.top_left().pair();
+    let (right, bottom) = selection.bottom_right().pair();
+
+    let edges = (left..=right)
+        .flat_map(|x| [(Vec2::new(x, top), S_N), (Vec2::new(x, bottom), S_S)])
+        .chain((top..=bottom).flat_map(|y| [(Vec2::new(left, y), S_W), (Vec2::new(right, y), S_E)]));
+
+    edges
+        .filter(|&(pos, _)| buf.visible(pos))
+        .filter_map(|(pos, dir)| trace(buf, selection, pos, dir, symbols))
+        .collect()
+}
+
+/// Blank out the cells of `connectors`, ready for them to be re-routed.
+pub(crate) fn detach(buf: &mut Buffer, connectors: &[Connector], symbols: &Symbols) {
+    for &pos in connectors.iter().flat_map(|conn| &conn.cells) {
+        buf.setv(true, pos, SP, symbols);
+    }
+}
+
+/// Redraw `connectors` from their far ends to the selection, after it has moved by
+/// `delta`. Both ends keep the direction they had before, so lines still meet the
+/// selection edge head on.
+pub(crate) fn reattach(buf: &mut Buffer, connectors: &[Connector], delta: XY<isize>, path_mode: PathMode, symbols: &Symbols) {
+    for conn in connectors {
+        let start = conn.start.saturating_add(delta);
+        let at_start = conn.dir.1 == 0;
+        let at_end = conn.end_dir.1 == 0;
+
+        if conn.tip_end {
+            draw_connector(buf, start, conn.end, (at_start, at_end), true, path_mode, symbols);
+        } else {
+            draw_connector(buf, conn.end, start, (at_end, at_start), conn.tip_start, path_mode, symbols);
+        }
+    }
+}
+
+/// Follow the line that leaves the selection edge at `pos` in direction `dir`.
+///
+/// Returns `None` if there is no line there, if it branches straight away, or if it
+/// leads back into the selection.
+fn trace(buf: &Buffer, selection: Rect, pos: Vec2, dir: (isize, isize), symbols: &Symbols) -> Option<Connector> {
+    let start = step(pos, dir)?;
+    let c = buf.get_char_at(start);
+    let tip_start = tip_dir(c, symbols) == Some(neg(dir));
+
+    if selection.contains(start) || !(connects(c, neg(dir), symbols) || tip_start) {
+        return None;
+    }
+
+    let mut cells = Vec::new();
+    let mut seen = HashSet::new();
+    let (mut pos, mut from) = (start, neg(dir));
+
+    loop {
+        let c = buf.get_char_at(pos);
+        let out: Vec<_> = [S_N, S_E, S_S, S_W]
+            .into_iter()
+            .filter(|&d| d != from && connects(c, d, symbols))
+            .filter_map(|d| {
+                let next = step(pos, d)?;
+                connects(buf.get_char_at(next), neg(d), symbols).then_some((next, d))
+            })
+            .collect();
+
+        // a junction joins other lines too, so it stays where it is
+        if pos != start && out.len() > 1 {
+            return Some(Connector { start, dir, end: pos, end_dir: from, cells, tip_start, tip_end: false });
+        }
+
+        cells.push(pos);
+        seen.insert(pos);
+
+        match out[..] {
+            [(next, _)] if selection.contains(next) => return None,
+            [(next, d)] if !seen.contains(&next) => (pos, from) = (next, neg(d)),
+            [] | [_] => {
+                let tip_end = pos != start && is_arrow_tip(c, symbols);
+                return Some(Connector { start, dir, end: pos, end_dir: from, cells, tip_start, tip_end });
+            }
+            _ => return None,
+        }
+    }
+}
+
+/// Draw a line from `src` to `dst`, with an arrow tip at `dst` if `arrow`.
+///
+/// In Snap90 mode, `horizontal` gives whether the line leaves `src` and arrives at
+/// `dst` horizontally. It bends once if those differ, and twice halfway along if not.
+fn draw_connector(buf: &mut Buffer, src: Vec2, dst: Vec2, horizontal: (bool, bool), arrow: bool, path_mode: PathMode, symbols: &Symbols) {
+    let last = match path_mode {
+        PathMode::Routed => before(&draw_path(buf, src, dst, symbols), dst),
+        PathMode::Orthogonal => before(&draw_route(buf, src, dst, symbols), dst),
+        _ => {
+            let bends = match horizontal {
+                (true, false) => vec![Vec2::new(dst.x, src.y)],
+                (false, true) => vec![Vec2::new(src.x, dst.y)],
+                (true, true) => {
+                    let x = (src.x + dst.x) / 2;
+                    vec![Vec2::new(x, src.y), Vec2::new(x, dst.y)]
+                }
+                (false, false) => {
+                    let y = (src.y + dst.y) / 2;
+                    vec![Vec2::new(src.x, y), Vec2::new(dst.x, y)]
+                }
+            };
+
+            let corners: Vec<_> = [src].into_iter().chain(bends).chain([dst]).collect();
+            let mut points = Vec::new();
+            for pair in corners.windows(2) {
+                points.extend(draw_line(buf, pair[0], pair[1], symbols));
+            }
+            fixup(buf, &points, arrow, symbols);
+
+            corners.into_iter().rev().find(|&p| p != dst).unwrap_or(src)
+        }
+    };
+
+    if arrow {
+        draw_arrow_tip(buf, last, dst, symbols);
+    }
+}
+
+/// Returns whether `c` joins up with its neighbour in direction `dir`.
+fn connects(c: char, dir: (isize, isize), symbols: &Symbols) -> bool {
+    match dir {
+        S_N => connects_up(c, symbols),
+        S_E => connects_right(c, symbols),
+        S_S => connects_down(c, symbols),
+        _ => connects_left(c, symbols),
+    }
+}
+
+/// Returns the direction that the arrow tip `c` points in.
+fn tip_dir(c: char, symbols: &Symbols) -> Option<(isize, isize)> {
+    match c {
+        c if c == symbols.n => Some(S_N),
+        c if c == symbols.e => Some(S_E),
+        c if c == symbols.s => Some(S_S),
+        c if c == symbols.w => Some(S_W),
+        _ => None,
+    }
+}
+
+fn step(pos: Vec2, dir: (isize, isize)) -> Option<Vec2> {
+    pos.checked_add(dir)
+}
+
+fn neg(dir: (isize, isize)) -> (isize, isize) {
+    (-dir.0, -dir.1)
+}
@@ -11,14 +11,16 @@ use crate::constants::CONSUMED;
 use crate::editor::{buffer::*, scroll::EditorCtx};
 use crate::config::{Options, Symbols};
 
-use super::super::{PathMode, Tool, simple_display, fn_on_event_drag, option, mouse_drag};
-use super::{draw_path, draw_line, snap90, snap45, fixup};
+use super::super::{PathMode, Bend, Throttle, Tool, fn_on_event_drag, option, mouse_drag};
+use super::{draw_path, draw_route, draw_snapped, fixup, fixup_diagonals};
 
 #[derive(Clone, Default)]
 pub(crate) struct LineTool {
     src: Option<Vec2>,
     dst: Option<Vec2>,
     path_mode: PathMode,
+    bend: Bend,
+    throttle: Throttle,
     symbols: Symbols,
 }
 
@@ -36,23 +38,25 @@ impl Tool for LineTool {
 
     fn_on_event_drag!(|t: &Self, buf: &mut Buffer| {
         let (src, dst) = option!(t.src, t.dst);
-        draw_line_on_buffer(buf, src, dst, t.path_mode, &t.symbols);
+        draw_line_on_buffer(buf, src, dst, t.path_mode, t.bend, &t.symbols);
     });
 }
 
-pub fn draw_line_on_buffer(buf: &mut Buffer, src: Vec2, dst: Vec2, path_mode: PathMode, symbols: &Symbols) {
-    if let PathMode::Routed = path_mode {
-        draw_path(buf, src, dst, symbols);
-        return;
+pub fn draw_line_on_buffer(buf: &mut Buffer, src: Vec2, dst: Vec2, path_mode: PathMode, bend: Bend, symbols: &Symbols) {
+    match path_mode {
+        PathMode::Routed => {
+            draw_path(buf, src, dst, symbols);
+            return;
+        }
+        PathMode::Orthogonal => {
+            draw_route(buf, src, dst, symbols);
+            return;
+        }
+        _ => {}
     }
 
-    let mid = match path_mode {
-        PathMode::Snap90 => snap90(buf, src, dst, symbols),
-        _ => snap45(src, dst),
-    };
+    let points = draw_snapped(buf, src, dst, path_mode, bend, symbols);
 
-    let mut points = draw_line(buf, src, mid, symbols);
-    points.extend(draw_line(buf, mid, dst, symbols));
-    
     fixup(buf, &points, false, symbols);
+    fixup_diagonals(buf, &points, symbols);
 }
\ No newline at end of file
@@ -0,0 +1,170 @@
+use cursive::{
+    event::{
+        Event, EventResult, Key, MouseButton::Left,
+        MouseEvent::{Hold, Press, Release},
+    },
+    Vec2
+};
+use std::{fmt, time::Instant};
+
+use crate::editor::{buffer::Buffer, scroll::EditorCtx, EditorMode};
+use crate::config::{Options, Symbols};
+use crate::constants::{CONSUMED, DOUBLE_CLICK, KEY_TOOL_ARROW};
+
+use super::super::{PathMode, Tool, mouse_drag};
+use super::{arrowtool::draw_arrow_tip, before, draw_segment, fixup, fixup_diagonals, linetool::LineTool};
+
+/// Draws a line through a series of waypoints, each segment using the path mode.
+#[derive(Clone, Default)]
+pub(crate) struct PolylineTool {
+    /// Waypoints placed so far.
+    points: Vec<Vec2>,
+    /// Whether to finish the line with an arrow tip.
+    arrow: bool,
+    /// Where and when the last waypoint was clicked, to spot a double click.
+    last_click: Option<(Vec2, Instant)>,
+    path_mode: PathMode,
+    symbols: Symbols,
+}
+
+impl fmt::Display for PolylineTool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.arrow {
+            true => write!(f, "Polyline Arrow: {:?}", self.path_mode),
+            false => write!(f, "Polyline: {:?}", self.path_mode),
+        }
+    }
+}
+
+impl PolylineTool {
+    /// Preview the waypoints, with a last segment leading on to `pending`.
+    fn render(&self, buf: &mut Buffer, pending: Vec2) {
+        let mut points = self.points.clone();
+        if points.last() != Some(&pending) {
+            points.push(pending);
+        }
+
+        draw_polyline_on_buffer(buf, &points, self.arrow, self.path_mode, &self.symbols);
+        buf.set_cursor(pending);
+    }
+
+    /// Add a waypoint at `pos`, unless the last one is already there.
+    fn add(&mut self, ctx: &mut EditorCtx<'_>, pos: Vec2) {
+        if self.points.last() != Some(&pos) {
+            self.points.push(pos);
+        }
+        ctx.preview(|buf| self.render(buf, pos));
+    }
+
+    /// Commit the waypoints placed so far as one edit, and return to Normal mode.
+    fn finish(&mut self, ctx: &mut EditorCtx<'_>) {
+        if self.points.len() > 1 {
+            ctx.clobber(&self.to_string(), |buf| {
+                draw_polyline_on_buffer(buf, &self.points, self.arrow, self.path_mode, &self.symbols)
+            });
+        } else {
+            ctx.preview(|_| {});
+        }
+        self.points.clear();
+        self.last_click = None;
+
+        let mut editor = ctx.0.get_inner_mut().write();
+        editor.mode = EditorMode::Normal;
+        editor.set_tool(LineTool::default());
+    }
+}
+
+impl Tool for PolylineTool {
+    fn load_opts(&mut self, opts: &Options) {
+        self.path_mode = opts.path_mode;
+        self.symbols = opts.symbols.clone();
+    }
+
+    fn on_event(&mut self, ctx: &mut EditorCtx<'_>, event: &Event) -> Option<EventResult> {
+        let cursor = ctx.0.get_inner_mut().read().buffer.get_cursor().unwrap_or_else(|| Vec2::new(0, 0));
+
+        match event {
+            Event::Mouse { .. } => {
+                let (pos, event) = mouse_drag!(ctx, event);
+
+                match event {
+                    Press(Left) => {
+                        let double = self.last_click.is_some_and(|(at, when)| at == pos && when.elapsed() < DOUBLE_CLICK);
+                        if double {
+                            self.finish(ctx);
+                        } else {
+                            self.last_click = Some((pos, Instant::now()));
+                            self.add(ctx, pos);
+                        }
+                    }
+
+                    Hold(Left) => ctx.preview(|buf| self.render(buf, pos)),
+
+                    // the waypoint was placed on press, a drag only previews the next one
+                    Release(Left) => {}
+
+                    _ => return None,
+                }
+            }
+
+            Event::Char(c) if c.is_ascii_digit() => {
+                let mut editor = ctx.0.get_inner_mut().write();
+                editor.pending_count.push(*c);
+            }
+
+            Event::Char(c @ ('h' | 'j' | 'k' | 'l')) => {
+                let count = {
+                    let mut editor = ctx.0.get_inner_mut().write();
+                    let count = editor.pending_count.parse::<usize>().unwrap_or(1).max(1);
+                    editor.pending_count.clear();
+                    count
+                };
+
+                let mut pos = cursor;
+                match *c {
+                    'h' => pos.x = pos.x.saturating_sub(count),
+                    'j' => pos.y += count,
+                    'k' => pos.y = pos.y.saturating_sub(count),
+                    _ => pos.x += count,
+                }
+                ctx.preview(|buf| self.render(buf, pos));
+                ctx.scroll_to_cursor();
+            }
+
+            Event::Char(KEY_TOOL_ARROW) => {
+                self.arrow = !self.arrow;
+                ctx.preview(|buf| self.render(buf, cursor));
+            }
+
+            Event::Char('\n') | Event::Key(Key::Enter) => self.add(ctx, cursor),
+
+            Event::Key(Key::Backspace) => {
+                self.points.pop();
+                ctx.preview(|buf| self.render(buf, cursor));
+            }
+
+            Event::Key(Key::Esc) => self.finish(ctx),
+
+            _ => return None,
+        }
+
+        CONSUMED
+    }
+}
+
+/// Draw a line through each of `points` in turn, with an arrow tip at the last one if
+/// `arrow`.
+pub fn draw_polyline_on_buffer(buf: &mut Buffer, points: &[Vec2], arrow: bool, path_mode: PathMode, symbols: &Symbols) {
+    let mut drawn = Vec::new();
+    for pair in points.windows(2) {
+        drawn.extend(draw_segment(buf, pair[0], pair[1], path_mode, symbols));
+    }
+
+    // junctions between segments are only known once every segment is drawn
+    fixup(buf, &drawn, arrow, symbols);
+    fixup_diagonals(buf, &drawn, symbols);
+
+    if let (true, [.., _, dst]) = (arrow, points) {
+        draw_arrow_tip(buf, before(&drawn, *dst), *dst, symbols);
+    }
+}
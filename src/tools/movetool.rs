@@ -7,15 +7,26 @@ use cursive::{
 };
 use std::fmt;
 
-use crate::editor::{buffer::Buffer, scroll::EditorCtx, EditorMode};
+use crate::editor::{buffer::Buffer, cell::Cell, scroll::EditorCtx, EditorMode};
 use crate::constants::{SP, CONSUMED, KEY_TOOL_ERASE};
 use crate::config::{Options, Symbols};
 use crate::tools::erasetool::erase_on_buffer;
-use super::{Tool, visible_cells, simple_display, mouse_drag, selecttool::SelectTool, lines::boxtool::BoxTool};
+use super::{
+    PathMode, Tool, visible_cells, simple_display, mouse_drag, selecttool::SelectTool,
+    lines::{boxtool::BoxTool, connector::{find_connectors, detach, reattach}},
+};
 
 pub(crate) struct MoveTool {
     pub selection: Rect,
     pub anchor: Vec2,
+    /// Cells dragged around instead of the contents of `selection`, which is then left
+    /// in place. Used for pasted text.
+    layer: Option<Vec<Cell>>,
+    /// Undo history description of the committed move.
+    desc: &'static str,
+    /// Whether lines attached to the selection are re-routed to follow it.
+    rubber_band: bool,
+    path_mode: PathMode,
     symbols: Symbols,
 }
 
@@ -24,13 +35,55 @@ impl MoveTool {
         Self {
             selection,
             anchor,
+            layer: None,
+            desc: "Move",
+            rubber_band: false,
+            path_mode: PathMode::default(),
+            symbols: Symbols::default(),
+        }
+    }
+
+    /// Returns a tool that moves `selection` along with any lines attached to its edges.
+    pub fn rubber_band(selection: Rect, anchor: Vec2) -> Self {
+        Self {
+            rubber_band: true,
+            ..Self::new(selection, anchor)
+        }
+    }
+
+    /// Returns a tool that drags the floating `cells` around, starting from `anchor`.
+    /// Committing it is recorded in the undo history as `desc`.
+    pub fn floating(cells: Vec<Cell>, anchor: Vec2, desc: &'static str) -> Self {
+        let (min, max) = cells.iter().fold((anchor, anchor), |(min, max), cell| {
+            (min.zip_map(cell.pos(), usize::min), max.zip_map(cell.pos(), usize::max))
+        });
+
+        Self {
+            selection: Rect::from_corners(min, max),
+            anchor,
+            layer: Some(cells),
+            desc,
+            rubber_band: false,
+            path_mode: PathMode::default(),
             symbols: Symbols::default(),
         }
     }
+
+    /// Preview the moved cells with the cursor at `to`.
+    pub fn render(&self, buf: &mut Buffer, to: Vec2) {
+        match &self.layer {
+            Some(cells) => stamp_on_buffer(buf, cells, self.anchor, to),
+            None if self.rubber_band => {
+                move_connected_on_buffer(buf, self.selection, self.anchor, to, self.path_mode, &self.symbols)
+            }
+            None => move_on_buffer(buf, self.selection, self.anchor, to, &self.symbols),
+        }
+    }
 }
 
 impl Tool for MoveTool {
     fn load_opts(&mut self, opts: &Options) {
+        self.path_mode = opts.path_mode;
         self.symbols = opts.symbols.clone();
     }
 
@@ -41,11 +94,11 @@ impl Tool for MoveTool {
 
                 match event {
                     Press(Left) | Hold(Left) => {
-                        ctx.preview(|buf| move_on_buffer(buf, self.selection, self.anchor, pos, &self.symbols));
+                        ctx.preview(|buf| self.render(buf, pos));
                     }
 
                     Release(Left) => {
-                        ctx.clobber(|buf| move_on_buffer(buf, self.selection, self.anchor, pos, &self.symbols));
+                        ctx.clobber(self.desc, |buf| self.render(buf, pos));
                         let mut editor = ctx.0.get_inner_mut().write();
                         editor.mode = EditorMode::Select(pos);
                         editor.set_tool(SelectTool::default());
@@ -53,13 +106,11 @@ impl Tool for MoveTool {
 
                     _ => return None,
                 }
-                return CONSUMED;
             }
 
             Event::Char(c) if c.is_ascii_digit() => {
                 let mut editor = ctx.0.get_inner_mut().write();
                 editor.pending_count.push(*c);
-                return CONSUMED;
             }
 
             Event::Char('h') | Event::Char('j') | Event::Char('k') | Event::Char('l') => {
@@ -81,15 +132,22 @@ impl Tool for MoveTool {
                     }
                 }
                 ctx.0.get_inner_mut().write().buffer.set_cursor(pos);
-                ctx.preview(|buf| move_on_buffer(buf, self.selection, self.anchor, pos, &self.symbols));
+                ctx.preview(|buf| self.render(buf, pos));
                 ctx.scroll_to_cursor();
-                return CONSUMED;
+            }
+
+            // a floating layer is not on the canvas yet, so erasing just drops it
+            Event::Char(KEY_TOOL_ERASE) if self.layer.is_some() => {
+                let mut editor = ctx.0.get_inner_mut().write();
+                let pos = editor.buffer.get_cursor().unwrap_or_else(|| Vec2::new(0, 0));
+                editor.mode = EditorMode::Select(pos);
+                editor.set_tool(SelectTool::default());
             }
 
             Event::Char(KEY_TOOL_ERASE) => {
                 let pos = ctx.0.get_inner_mut().read().buffer.get_cursor().unwrap_or_else(|| Vec2::new(0, 0));
 
-                ctx.clobber(|buf| {
+                ctx.clobber("Erase", |buf| {
                     erase_on_buffer(buf, self.selection.top_left(), self.selection.bottom_right(), &self.symbols);
                     buf.set_cursor(pos);
                 });
@@ -97,31 +155,30 @@ impl Tool for MoveTool {
                 let mut editor = ctx.0.get_inner_mut().write();
                 editor.mode = EditorMode::Select(pos);
                 editor.set_tool(SelectTool::default());
-                return CONSUMED;
             }
 
             Event::Char('\n') | Event::Key(Key::Enter) => {
                 let pos = ctx.0.get_inner_mut().read().buffer.get_cursor().unwrap_or_else(|| Vec2::new(0, 0));
                 
-                ctx.clobber(|buf| move_on_buffer(buf, self.selection, self.anchor, pos, &self.symbols));
+                ctx.clobber(self.desc, |buf| self.render(buf, pos));
                 let mut editor = ctx.0.get_inner_mut().write();
                 editor.mode = EditorMode::Select(pos);
                 editor.set_tool(SelectTool::default());
-                return CONSUMED;
             }
 
             Event::Key(Key::Esc) => {
                 let pos = ctx.0.get_inner_mut().read().buffer.get_cursor().unwrap_or_else(|| Vec2::new(0, 0));
                 
-                ctx.clobber(|buf| move_on_buffer(buf, self.selection, self.anchor, pos, &self.symbols));
+                ctx.clobber(self.desc, |buf| self.render(buf, pos));
                 let mut editor = ctx.0.get_inner_mut().write();
                 editor.mode = EditorMode::Normal;
                 editor.set_tool(BoxTool::default());
-                return CONSUMED;
             }
 
             _ => return None,
         }
+
+        CONSUMED
     }
 
     fn move_info(&self) -> Option<(Rect, Vec2)> {
@@ -141,8 +198,36 @@ pub fn move_on_buffer(buf: &mut Buffer, selection: Rect, from: Vec2, to: Vec2, s
     let delta = to.signed() - from.signed();
 
     for cell in state.into_iter().map(|cell| cell.translate(delta)) {
-        buf.setv(true, cell.pos(), cell.c(), symbols);
+        buf.set_glyph(cell.pos, cell.glyph);
     }
     
     buf.set_cursor(to);
 }
+
+/// Like `move_on_buffer`, but lines and arrows attached to the edges of `selection` are
+/// re-routed from their far ends to follow it.
+pub fn move_connected_on_buffer(buf: &mut Buffer, selection: Rect, from: Vec2, to: Vec2, path_mode: PathMode, symbols: &Symbols) {
+    if from == to {
+        return move_on_buffer(buf, selection, from, to, symbols);
+    }
+
+    let connectors = find_connectors(buf, selection, symbols);
+
+    detach(buf, &connectors, symbols);
+    move_on_buffer(buf, selection, from, to, symbols);
+    reattach(buf, &connectors, to.signed() - from.signed(), path_mode, symbols);
+
+    buf.set_cursor(to);
+}
+
+/// Place `cells` on the buffer, moved by the offset from `from` to `to`, over whatever
+/// is already there.
+pub fn stamp_on_buffer(buf: &mut Buffer, cells: &[Cell], from: Vec2, to: Vec2) {
+    let delta = to.signed() - from.signed();
+
+    for cell in cells.iter().map(|cell| cell.clone().translate(delta)) {
+        buf.set_glyph(cell.pos, cell.glyph);
+    }
+
+    buf.set_cursor(to);
+}
@@ -0,0 +1,159 @@
+use cursive::{
+    event::{
+        Event, EventResult, Key, MouseButton::Left,
+        MouseEvent::{Hold, Press, Release},
+    },
+    Rect, Vec2
+};
+use std::fmt;
+
+use crate::editor::{buffer::Buffer, scroll::EditorCtx, EditorMode};
+use crate::constants::{SP, CONSUMED};
+use crate::config::{Options, Symbols};
+use crate::utils::shapes::BoxShape;
+use super::{Tool, simple_display, mouse_drag, lines::boxtool::{BoxTool, draw_box_on_buffer}};
+
+pub(crate) struct ResizeTool {
+    shape: BoxShape,
+    /// Corner that stays in place.
+    anchor: Vec2,
+    /// Corner that is dragged, as it was before resizing.
+    grab: Vec2,
+    /// Whether the dragged corner follows the cursor horizontally and vertically.
+    axes: (bool, bool),
+    symbols: Symbols,
+}
+
+impl ResizeTool {
+    /// Returns a tool that resizes `shape` by the edge or corner at `pos`. Positions
+    /// inside the box grab its bottom right corner.
+    pub fn grab(shape: BoxShape, pos: Vec2) -> Self {
+        let (left, top) = shape.rect.top_left().pair();
+        let (right, bottom) = shape.rect.bottom_right().pair();
+
+        // (anchor, grab) along each axis
+        let edge = |p, lo, hi| match p {
+            p if p == lo => Some((hi, lo)),
+            p if p == hi => Some((lo, hi)),
+            _ => None,
+        };
+
+        let (x, y) = match (edge(pos.x, left, right), edge(pos.y, top, bottom)) {
+            (None, None) => (Some((left, right)), Some((top, bottom))),
+            edges => edges,
+        };
+
+        Self {
+            shape,
+            anchor: Vec2::new(x.map_or(left, |e| e.0), y.map_or(top, |e| e.0)),
+            grab: Vec2::new(x.map_or(right, |e| e.1), y.map_or(bottom, |e| e.1)),
+            axes: (x.is_some(), y.is_some()),
+            symbols: Symbols::default(),
+        }
+    }
+
+    /// Returns where the cursor starts, on the grabbed edge or corner.
+    pub fn handle(&self, pos: Vec2) -> Vec2 {
+        Vec2::new(
+            if self.axes.0 { self.grab.x } else { pos.x },
+            if self.axes.1 { self.grab.y } else { pos.y },
+        )
+    }
+
+    /// Returns the resized box with the grabbed edge or corner at `to`.
+    fn target(&self, to: Vec2) -> Rect {
+        let corner = Vec2::new(
+            if self.axes.0 { to.x } else { self.grab.x },
+            if self.axes.1 { to.y } else { self.grab.y },
+        );
+
+        Rect::from_corners(self.anchor, corner)
+    }
+
+    /// Preview the resized box with the cursor at `to`.
+    pub fn render(&self, buf: &mut Buffer, to: Vec2) {
+        resize_box_on_buffer(buf, &self.shape, self.target(to), &self.symbols);
+        buf.set_cursor(to);
+    }
+
+    fn commit(&self, ctx: &mut EditorCtx<'_>, to: Vec2) {
+        // leave the box untouched if it was only clicked on
+        if self.target(to) == self.shape.rect {
+            ctx.preview(|buf| buf.set_cursor(to));
+        } else {
+            ctx.clobber("Resize", |buf| self.render(buf, to));
+        }
+
+        let mut editor = ctx.0.get_inner_mut().write();
+        editor.mode = EditorMode::Normal;
+        editor.set_tool(BoxTool::default());
+    }
+}
+
+impl Tool for ResizeTool {
+    fn load_opts(&mut self, opts: &Options) {
+        self.symbols = opts.symbols.clone();
+    }
+
+    fn on_event(&mut self, ctx: &mut EditorCtx<'_>, event: &Event) -> Option<EventResult> {
+        match event {
+            Event::Mouse { .. } => {
+                let (pos, event) = mouse_drag!(ctx, event);
+
+                match event {
+                    Press(Left) | Hold(Left) => {
+                        ctx.preview(|buf| self.render(buf, pos));
+                    }
+
+                    Release(Left) => self.commit(ctx, pos),
+
+                    _ => return None,
+                }
+            }
+
+            Event::Char(c) if c.is_ascii_digit() => {
+                let mut editor = ctx.0.get_inner_mut().write();
+                editor.pending_count.push(*c);
+            }
+
+            Event::Char(c @ ('h' | 'j' | 'k' | 'l')) => {
+                let count = {
+                    let mut editor = ctx.0.get_inner_mut().write();
+                    let count = editor.pending_count.parse::<usize>().unwrap_or(1).max(1);
+                    editor.pending_count.clear();
+                    count
+                };
+
+                let mut pos = ctx.0.get_inner_mut().read().buffer.get_cursor().unwrap_or_else(|| Vec2::new(0, 0));
+                match *c {
+                    'h' => pos.x = pos.x.saturating_sub(count),
+                    'j' => pos.y += count,
+                    'k' => pos.y = pos.y.saturating_sub(count),
+                    _ => pos.x += count,
+                }
+                ctx.preview(|buf| self.render(buf, pos));
+                ctx.scroll_to_cursor();
+            }
+
+            Event::Char('\n') | Event::Key(Key::Enter) | Event::Key(Key::Esc) => {
+                let pos = ctx.0.get_inner_mut().read().buffer.get_cursor().unwrap_or_else(|| Vec2::new(0, 0));
+                self.commit(ctx, pos);
+            }
+
+            _ => return None,
+        }
+
+        CONSUMED
+    }
+}
+
+simple_display! { ResizeTool, "Resize" }
+
+/// Redraw the outline of `shape` as the box `to`, keeping everything inside it.
+pub fn resize_box_on_buffer(buf: &mut Buffer, shape: &BoxShape, to: Rect, symbols: &Symbols) {
+    for pos in shape.outline() {
+        buf.setv(true, pos, SP, symbols);
+    }
+
+    draw_box_on_buffer(buf, to.top_left(), to.bottom_right(), symbols);
+}
@@ -2,21 +2,61 @@ use cursive::{
     event::{
         Event, EventResult, Key, MouseButton::Left, MouseEvent::Press,
     },
-    Vec2,
+    Rect, Vec2,
 };
 use std::{cmp::min, fmt};
-use super::{Tool, simple_display, option};
+use super::{Tool, option};
+use super::lines::boxtool::draw_box_on_buffer;
+use super::resizetool::resize_box_on_buffer;
 
-use crate::editor::{buffer::*, scroll::EditorCtx};
-use crate::constants::CONSUMED;
+use crate::editor::{buffer::*, cell::Glyph, scroll::EditorCtx};
+use crate::constants::{SP, CONSUMED, LABEL_PADDING};
+use crate::utils::{graphemes, shapes::{BoxShape, Label, Shapes}};
 use crate::config::{Options, Symbols};
 
+/// How each row of text is placed within the width of the block or box.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) enum Align {
+    #[default]
+    Left,
+    Centre,
+    Right,
+}
+
+/// The box that text is written into, if any.
+#[derive(Copy, Clone, Debug, Default)]
+enum Frame {
+    /// Plain text, starting at the cursor.
+    #[default]
+    None,
+    /// A new box, drawn around the text and sized to fit it.
+    Label,
+    /// An existing box. Text wraps to fit inside it, and the box grows downwards if
+    /// there are more rows than fit.
+    Box(BoxShape),
+}
+
+/// A row of laid out text: the typed line it comes from and the clusters on it.
+struct Row {
+    line: usize,
+    start: usize,
+    end: usize,
+}
+
 #[derive(Clone)]
 pub(crate) struct TextTool {
     src: Option<Vec2>,
     cursor_active: bool,
-    buffer: Vec<Vec<char>>,
+    /// Typed lines, one grapheme cluster per glyph.
+    buffer: Vec<Vec<Glyph>>,
     cursor: Vec2,
+    frame: Frame,
+    align: Align,
+    /// Whether typing replaces the cluster under the cursor instead of inserting,
+    /// including any text already on the canvas past the end of the line.
+    replace: bool,
+    /// Cells of the existing text block being edited, blanked before it is redrawn.
+    erase: Vec<Vec2>,
     symbols: Symbols,
 }
 
@@ -27,12 +67,27 @@ impl Default for TextTool {
             cursor_active: false,
             buffer: vec![],
             cursor: Vec2::new(0, 0),
+            frame: Frame::None,
+            align: Align::Left,
+            replace: false,
+            erase: vec![],
             symbols: Symbols::default(),
         }
     }
 }
 
-simple_display! { TextTool, "Text" }
+impl fmt::Display for TextTool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.frame {
+            Frame::None => write!(f, "Text: {:?}", self.align)?,
+            _ => write!(f, "Label: {:?}", self.align)?,
+        }
+        if self.replace {
+            write!(f, ", Replace")?;
+        }
+        Ok(())
+    }
+}
 
 impl Tool for TextTool {
     fn load_opts(&mut self, opts: &Options) {
@@ -49,21 +104,25 @@ impl Tool for TextTool {
                 ..
             } => {
                 if self.cursor_active {
-                    ctx.clobber(|buf| self.render(buf));
+                    ctx.clobber("Text", |buf| self.render(buf));
                 }
                 self.src = Some(position);
                 self.cursor_active = true;
                 self.buffer.clear();
                 self.buffer.push(vec![]);
                 self.cursor = Vec2::new(0, 0);
+                self.erase.clear();
+                if let Frame::Box(_) = self.frame {
+                    self.frame = Frame::None;
+                }
+                self.attach(&ctx.0.get_inner_mut().read().buffer);
                 ctx.preview(|buf| self.render(buf));
             }
 
             _ if !self.cursor_active => return None,
 
             Event::Char(c) => {
-                self.buffer[*y].insert(*x, c);
-                *x += 1;
+                self.type_char(c, &ctx.0.get_inner_mut().read().buffer);
                 ctx.preview(|buf| self.render(buf));
                 ctx.scroll_to_cursor();
             }
@@ -94,6 +153,57 @@ impl Tool for TextTool {
                 ctx.scroll_to_cursor();
             }
 
+            Event::Key(Key::Home) => {
+                *x = 0;
+                ctx.preview(|buf| self.render(buf));
+                ctx.scroll_to_cursor();
+            }
+
+            Event::Key(Key::End) => {
+                *x = self.buffer[*y].len();
+                ctx.preview(|buf| self.render(buf));
+                ctx.scroll_to_cursor();
+            }
+
+            Event::Ctrl(Key::Left) => {
+                if *x == 0 && *y > 0 {
+                    *y -= 1;
+                    *x = self.buffer[*y].len();
+                } else {
+                    let line = &self.buffer[*y];
+                    while *x > 0 && is_space(&line[*x - 1]) {
+                        *x -= 1;
+                    }
+                    while *x > 0 && !is_space(&line[*x - 1]) {
+                        *x -= 1;
+                    }
+                }
+                ctx.preview(|buf| self.render(buf));
+                ctx.scroll_to_cursor();
+            }
+
+            Event::Ctrl(Key::Right) => {
+                if *x == self.buffer[*y].len() && *y + 1 < self.buffer.len() {
+                    *y += 1;
+                    *x = 0;
+                } else {
+                    let line = &self.buffer[*y];
+                    while *x < line.len() && !is_space(&line[*x]) {
+                        *x += 1;
+                    }
+                    while *x < line.len() && is_space(&line[*x]) {
+                        *x += 1;
+                    }
+                }
+                ctx.preview(|buf| self.render(buf));
+                ctx.scroll_to_cursor();
+            }
+
+            Event::Key(Key::Ins) => {
+                self.replace = !self.replace;
+                ctx.preview(|buf| self.render(buf));
+            }
+
             Event::Key(Key::Enter) => {
                 let next = self.buffer[*y].split_off(*x);
                 self.buffer.insert(*y + 1, next);
@@ -103,7 +213,18 @@ impl Tool for TextTool {
                 ctx.scroll_to_cursor();
             }
 
-            Event::Key(Key::Backspace) | Event::Key(Key::Del) => {
+            Event::Key(Key::Del) => {
+                if *x < self.buffer[*y].len() {
+                    self.buffer[*y].remove(*x);
+                } else if *y + 1 < self.buffer.len() {
+                    let mut next = self.buffer.remove(*y + 1);
+                    self.buffer[*y].append(&mut next);
+                }
+                ctx.preview(|buf| self.render(buf));
+                ctx.scroll_to_cursor();
+            }
+
+            Event::Key(Key::Backspace) => {
                 if *x > 0 {
                     self.buffer[*y].remove(*x - 1);
                     *x -= 1;
@@ -117,8 +238,18 @@ impl Tool for TextTool {
                 ctx.scroll_to_cursor();
             }
 
+            Event::Key(Key::Tab) => {
+                self.align = match self.align {
+                    Align::Left => Align::Centre,
+                    Align::Centre => Align::Right,
+                    Align::Right => Align::Left,
+                };
+                ctx.preview(|buf| self.render(buf));
+                ctx.scroll_to_cursor();
+            }
+
             Event::Key(Key::Esc) => {
-                ctx.clobber(|buf| self.render(buf));
+                ctx.clobber("Text", |buf| self.render(buf));
                 self.reset();
                 return None;
             }
@@ -136,22 +267,246 @@ impl TextTool {
             src: Some(pos),
             cursor_active: true,
             buffer: vec![vec![]], // Start with one empty line
-            cursor: Vec2::new(0, 0),
-            symbols: Symbols::default(),
+            ..Self::default()
+        }
+    }
+
+    /// Returns a tool that draws a box around the text as it is typed.
+    pub(crate) fn label(pos: Vec2) -> Self {
+        Self { frame: Frame::Label, ..Self::new(pos) }
+    }
+
+    /// Write plain text into the smallest box the start position is inside of, if
+    /// any. Starting on an existing block of text picks it up to be edited in place.
+    pub(crate) fn attach(&mut self, buf: &Buffer) {
+        let pos = option!(self.src);
+        if let Frame::Label = self.frame {
+            return;
+        }
+
+        let shapes = Shapes::scan(buf, &self.symbols);
+        if let Some(shape) = shapes.box_at(pos).filter(|b| b.inside(pos)) {
+            self.frame = Frame::Box(*shape);
+        }
+
+        let block = shapes.block_at(pos);
+        let (top, left) = match (block.first(), block.iter().map(|label| label.pos.x).min()) {
+            (Some(first), Some(left)) => (first.pos.y, left),
+            _ => return,
+        };
+
+        // text in a box is laid out again from its labels, elsewhere lines keep their indent
+        let boxed = matches!(self.frame, Frame::Box(_));
+        let start = |label: &Label| if boxed { label.pos.x } else { left };
+
+        self.buffer = block
+            .iter()
+            .map(|label| {
+                (start(label)..label.pos.x + label.width)
+                    .map(|x| buf.glyph_at(Vec2::new(x, label.pos.y)))
+                    .filter(|g| *g != Glyph::Wide)
+                    .collect()
+            })
+            .collect();
+        self.erase = block.iter().flat_map(|label| label.cells()).collect();
+        self.src = Some(Vec2::new(left, top));
+
+        let y = pos.y - top;
+        let mut col = start(block[y]);
+        let x = self.buffer[y]
+            .iter()
+            .take_while(|g| {
+                col += graphemes::width(g);
+                col <= pos.x
+            })
+            .count();
+        self.cursor = Vec2::new(x, y);
+
+        if let Frame::Box(shape) = self.frame {
+            self.align = detect_align(&block, shape.rect);
+        }
+    }
+
+    /// Type `c` at the cursor. Combining marks and joiners extend the cluster before it.
+    fn type_char(&mut self, c: char, buf: &Buffer) {
+        self.seed(buf);
+
+        let Vec2 { x, y } = &mut self.cursor;
+        let line = &mut self.buffer[*y];
+        match x.checked_sub(1).and_then(|i| graphemes::join(&line[i], c)) {
+            Some(g) => line[*x - 1] = g,
+            None if self.replace && *x < line.len() => {
+                line[*x] = Glyph::Char(c);
+                *x += 1;
+            }
+            None => {
+                line.insert(*x, Glyph::Char(c));
+                *x += 1;
+            }
+        }
+    }
+
+    /// In replace mode, take the run of text on the canvas just past the end of the
+    /// line into it, so that typing there replaces it rather than writing on top.
+    ///
+    /// Only plain left aligned text is laid out where it was typed, so other text is
+    /// never seeded.
+    fn seed(&mut self, buf: &Buffer) {
+        let src = option!(self.src);
+        if !self.replace || !matches!(self.frame, Frame::None) || self.align != Align::Left {
+            return;
+        }
+
+        let Vec2 { x, y } = self.cursor;
+        let line = &self.buffer[y];
+        if x < line.len() {
+            return;
+        }
+
+        let mut pos = src + (line.iter().map(graphemes::width).sum::<usize>(), y);
+        let mut seeded = Vec::new();
+        while let Some(g) = buf.getv(pos).filter(|g| !g.is_whitespace() && !self.erase.contains(&pos)) {
+            // only whole clusters are taken, never the second half of one
+            if g != Glyph::Wide {
+                seeded.push(g);
+            } else if seeded.is_empty() {
+                break;
+            }
+            self.erase.push(pos);
+            pos = pos.map_x(|x| x + 1);
+        }
+
+        self.buffer[y].extend(seeded);
+    }
+
+    /// Returns the top left of the text, and the width that rows are aligned within.
+    /// Rows are only wrapped inside an existing box.
+    fn area(&self, src: Vec2, rows: &[Row]) -> (Vec2, usize, bool) {
+        let widest = rows.iter().map(|row| self.width(row)).max().unwrap_or(0);
+
+        match self.frame {
+            Frame::None => (src, widest, false),
+            Frame::Label => (src + (1 + LABEL_PADDING, 1), widest.max(1), false),
+            Frame::Box(shape) => {
+                let (pad, width) = inner_width(shape.rect);
+                (Vec2::new(shape.rect.left() + 1 + pad, src.y), width, true)
+            }
+        }
+    }
+
+    /// Returns the columns taken up by `row`, leaving out trailing spaces.
+    fn width(&self, row: &Row) -> usize {
+        let line = &self.buffer[row.line][row.start..row.end];
+        let len = line.iter().rposition(|g| !is_space(g)).map_or(0, |i| i + 1);
+        line[..len].iter().map(graphemes::width).sum()
+    }
+
+    /// Lay out the typed lines, wrapping them at word boundaries to fit `width`.
+    fn rows(&self, width: Option<usize>) -> Vec<Row> {
+        let mut rows = Vec::new();
+
+        for (i, line) in self.buffer.iter().enumerate() {
+            let (mut start, mut col, mut space) = (0, 0, None);
+
+            for (j, g) in line.iter().enumerate() {
+                let w = graphemes::width(g);
+
+                // spaces hang off the end of a row rather than starting the next one
+                if width.is_some_and(|max| col + w > max) && j > start && !is_space(g) {
+                    let end = space.map_or(j, |s| s + 1);
+                    rows.push(Row { line: i, start, end });
+                    start = end;
+                    col = line[start..j].iter().map(graphemes::width).sum();
+                    space = None;
+                }
+
+                if is_space(g) {
+                    space = Some(j);
+                }
+                col += w;
+            }
+
+            rows.push(Row { line: i, start, end: line.len() });
+        }
+
+        rows
+    }
+
+    /// Returns the column that `row` starts at within a block `width` columns wide.
+    fn indent(&self, row: &Row, width: usize) -> usize {
+        let spare = width.saturating_sub(self.width(row));
+
+        match self.align {
+            Align::Left => 0,
+            Align::Centre => spare / 2,
+            Align::Right => spare,
         }
     }
 
     fn render(&self, buf: &mut Buffer) {
         let src = option!(self.src);
 
-        for (y, line) in self.buffer.iter().enumerate() {
-            for (x, c) in line.iter().enumerate() {
-                let pos = Vec2::new(x, y) + src;
-                buf.setv(true, pos, *c, &self.symbols);
+        let wrap = match self.frame {
+            Frame::Box(shape) => Some(inner_width(shape.rect).1),
+            _ => None,
+        };
+        let rows = self.rows(wrap);
+        let (origin, width, wrapped) = self.area(src, &rows);
+
+        for &pos in &self.erase {
+            buf.setv(true, pos, SP, &self.symbols);
+        }
+
+        match self.frame {
+            Frame::None => {}
+            Frame::Label => {
+                let size = Vec2::new(width + 2 * LABEL_PADDING + 2, rows.len() + 2);
+                draw_box_on_buffer(buf, src, src + size - (1, 1), &self.symbols);
+            }
+            Frame::Box(shape) => {
+                let bottom = shape.rect.bottom().max(origin.y + rows.len());
+                if bottom != shape.rect.bottom() {
+                    // clear whatever the box grows over, so it only holds the text
+                    for y in shape.rect.bottom() + 1..bottom {
+                        for x in shape.rect.left() + 1..shape.rect.right() {
+                            buf.setv(true, Vec2::new(x, y), SP, &self.symbols);
+                        }
+                    }
+                    let to = Rect::from_corners(shape.rect.top_left(), (shape.rect.right(), bottom));
+                    resize_box_on_buffer(buf, &shape, to, &self.symbols);
+                }
             }
         }
 
-        buf.set_cursor(self.cursor + src);
+        for (y, row) in rows.iter().enumerate() {
+            let mut x = self.indent(row, width);
+            for g in &self.buffer[row.line][row.start..row.end] {
+                let w = graphemes::width(g);
+                // hanging spaces would otherwise land on the box outline
+                if wrapped && x + w > width {
+                    break;
+                }
+                let pos = Vec2::new(x, y) + origin;
+                buf.set_glyph(pos, g.clone());
+                if w == 2 {
+                    buf.set_glyph(pos.map_x(|x| x + 1), Glyph::Wide);
+                }
+                x += w;
+            }
+        }
+
+        let Vec2 { x, y } = self.cursor;
+        let (y, row) = rows
+            .iter()
+            .enumerate()
+            .rfind(|(_, row)| row.line == y && row.start <= x)
+            .unwrap_or((0, &rows[0]));
+        let line = &self.buffer[row.line];
+        let mut col = self.indent(row, width) + line[row.start..x].iter().map(graphemes::width).sum::<usize>();
+        if wrapped {
+            col = col.min(width);
+        }
+        buf.set_cursor(Vec2::new(col, y) + origin);
     }
 
     fn reset(&mut self) {
@@ -159,5 +514,101 @@ impl TextTool {
         self.cursor_active = false;
         self.buffer.clear();
         self.cursor = Vec2::new(0, 0);
+        self.erase.clear();
+        if let Frame::Box(_) = self.frame {
+            self.frame = Frame::None;
+        }
+    }
+}
+
+fn is_space(g: &Glyph) -> bool {
+    *g == Glyph::Char(' ')
+}
+
+/// Returns the padding either side of the text inside `rect`, and the columns left
+/// for the text itself.
+fn inner_width(rect: Rect) -> (usize, usize) {
+    let inner = rect.width().saturating_sub(2);
+    let pad = if inner > 2 * LABEL_PADDING { LABEL_PADDING } else { 0 };
+
+    (pad, (inner - 2 * pad).max(1))
+}
+
+/// Returns the alignment that places each of the labels in `block` where they are
+/// within the box `rect`, or left alignment if there is none.
+fn detect_align(block: &[&Label], rect: Rect) -> Align {
+    let (pad, width) = inner_width(rect);
+    let left = rect.left() + 1 + pad;
+
+    // columns to the left and right of each label within the text area
+    let gaps: Vec<_> = block
+        .iter()
+        .map(|label| {
+            let lead = label.pos.x.saturating_sub(left);
+            (lead, width.saturating_sub(lead + label.width))
+        })
+        .collect();
+
+    if gaps.iter().all(|&(lead, _)| lead == 0) {
+        Align::Left
+    } else if gaps.iter().all(|&(_, trail)| trail == 0) {
+        Align::Right
+    } else if gaps.iter().all(|&(lead, trail)| lead == trail || lead + 1 == trail) {
+        Align::Centre
+    } else {
+        Align::Left
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the canvas after typing `keys` with a text tool started at `pos` on
+    /// `text`, in replace mode if `replace` is set.
+    fn typed(text: &str, pos: Vec2, replace: bool, keys: &str) -> String {
+        let mut buf = Buffer::read_from(text.as_bytes(), 8).unwrap();
+        let mut tool = TextTool::new(pos);
+        tool.replace = replace;
+        tool.attach(&buf);
+
+        for c in keys.chars() {
+            tool.type_char(c, &buf);
+        }
+        tool.render(&mut buf);
+        buf.flush_edits();
+        buf.iter("").collect()
+    }
+
+    #[test]
+    fn replace_types_over_the_canvas() {
+        assert_eq!(typed("abc  xyz\n", Vec2::new(3, 0), true, "123"), "abc123yz\n");
+        assert_eq!(typed("abc  xyz\n", Vec2::new(3, 0), false, "123"), "abc123yz\n");
+        assert_eq!(typed("ab  中d\n", Vec2::new(2, 0), true, "123"), "ab123d \n");
+    }
+
+    #[test]
+    fn replaced_canvas_text_can_be_edited() {
+        let mut buf = Buffer::read_from("abc  xyz\n".as_bytes(), 8).unwrap();
+        let mut tool = TextTool::new(Vec2::new(3, 0));
+        tool.replace = true;
+        tool.attach(&buf);
+
+        for c in "123".chars() {
+            tool.type_char(c, &buf);
+        }
+        tool.replace = false;
+        tool.type_char('4', &buf);
+
+        tool.render(&mut buf);
+        buf.flush_edits();
+        assert_eq!(buf.iter("").collect::<String>(), "abc1234yz\n");
+    }
+
+    #[test]
+    fn blocks_are_edited_in_place() {
+        assert_eq!(typed("hello world\n", Vec2::new(0, 0), false, "J"), "Jhello world\n");
+        assert_eq!(typed("hello world\n", Vec2::new(0, 0), true, "J"), "Jello world\n");
+        assert_eq!(typed("ab 中d\n", Vec2::new(2, 0), true, "12"), "ab12d \n");
     }
 }
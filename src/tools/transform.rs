@@ -0,0 +1,215 @@
+use cursive::{Rect, Vec2};
+use std::fmt;
+
+use crate::editor::{buffer::Buffer, cell::Glyph};
+use crate::constants::SP;
+use crate::config::Symbols;
+use crate::utils::{graphemes, junctions::{fixup_point, is_joinable}};
+use super::visible_cells;
+
+/// A transformation of the contents of a selection.
+#[derive(Copy, Clone, Debug)]
+pub(crate) enum Transform {
+    /// Rotate by 90° clockwise around the top left corner.
+    Rotate,
+    /// Mirror left to right.
+    FlipH,
+    /// Mirror top to bottom.
+    FlipV,
+}
+
+impl fmt::Display for Transform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Transform::Rotate => write!(f, "Rotate"),
+            Transform::FlipH => write!(f, "Flip Horizontal"),
+            Transform::FlipV => write!(f, "Flip Vertical"),
+        }
+    }
+}
+
+impl Transform {
+    /// Returns the rect that the contents of `area` end up in.
+    pub(crate) fn apply_rect(self, area: Rect) -> Rect {
+        match self {
+            Transform::Rotate => Rect::from_size(area.top_left(), (area.height(), area.width())),
+            Transform::FlipH | Transform::FlipV => area,
+        }
+    }
+
+    /// Returns where the character at `pos` within `area`, `width` columns wide, is
+    /// placed. Returns `None` if that falls off the top or left of the canvas.
+    fn apply_pos(self, area: Rect, pos: Vec2, width: usize) -> Option<Vec2> {
+        let (left, top) = area.top_left().signed().pair();
+        let (right, bottom) = area.bottom_right().signed().pair();
+        let (x, y) = pos.signed().pair();
+
+        let (x, y) = match self {
+            Transform::Rotate => (left + bottom - y, top + x - left),
+            Transform::FlipH => (left + right - (x + width as isize - 1), y),
+            Transform::FlipV => (x, top + bottom - y),
+        };
+
+        Some(Vec2::new(x.try_into().ok()?, y.try_into().ok()?))
+    }
+
+    /// Returns the glyph that `c` turns into, so that lines, corners, junctions and arrow
+    /// tips keep pointing the right way.
+    fn remap(self, c: char, s: &Symbols) -> char {
+        let diagonals = [vec![s.diag, s.gaid], vec![s.diag2, s.gaid2]];
+
+        let cycles: Vec<Vec<char>> = match self {
+            Transform::Rotate => vec![
+                vec![s.n, s.e, s.s, s.w],
+                vec![s.tlcorn, s.trcorn, s.brcorn, s.blcorn],
+                vec![s.lhinter, s.tvinter, s.rhinter, s.bvinter],
+                vec![s.hline, s.vline],
+                vec![s.dash, s.pipe],
+            ],
+            Transform::FlipH => vec![
+                vec![s.w, s.e],
+                vec![s.tlcorn, s.trcorn],
+                vec![s.blcorn, s.brcorn],
+                vec![s.lhinter, s.rhinter],
+            ],
+            Transform::FlipV => vec![
+                vec![s.n, s.s],
+                vec![s.tlcorn, s.blcorn],
+                vec![s.trcorn, s.brcorn],
+                vec![s.tvinter, s.bvinter],
+            ],
+        };
+
+        cycles
+            .iter()
+            .chain(diagonals.iter())
+            .find_map(|cycle| {
+                let i = cycle.iter().position(|&g| g == c)?;
+                Some(cycle[(i + 1) % cycle.len()])
+            })
+            .unwrap_or(c)
+    }
+}
+
+/// Apply `transform` to the visible cells within `area`, then repair the junctions
+/// around the edges of both the original and the transformed rect.
+pub fn transform_on_buffer(buf: &mut Buffer, area: Rect, transform: Transform, symbols: &Symbols) {
+    let state: Vec<_> = visible_cells(buf, (area.top_left(), area.bottom_right()), symbols).collect();
+
+    for cell in state.iter() {
+        buf.setv(true, cell.pos(), SP, symbols);
+    }
+
+    for cell in state.iter().filter(|cell| *cell.glyph() != Glyph::Wide) {
+        let width = graphemes::width(cell.glyph());
+        let pos = match transform.apply_pos(area, cell.pos(), width) {
+            Some(pos) => pos,
+            None => continue,
+        };
+
+        let glyph = match *cell.glyph() {
+            Glyph::Char(c) => Glyph::Char(transform.remap(c, symbols)),
+            ref g => g.clone(),
+        };
+        buf.set_glyph(pos, glyph);
+        if width > 1 {
+            buf.set_glyph(pos.map_x(|x| x + 1), Glyph::Wide);
+        }
+    }
+
+    let mut change_set = Vec::new();
+
+    for rect in [area, transform.apply_rect(area)] {
+        let outer = Rect::from_corners(rect.top_left().map(|v| v.saturating_sub(1)), rect.bottom_right() + (1, 1));
+
+        for pos in outline(rect).chain(outline(outer)) {
+            let new_char = fixup_point(pos, buf, symbols);
+
+            if is_joinable(new_char, symbols) && new_char != buf.get_char_at(pos) {
+                change_set.push((pos, new_char));
+            }
+        }
+    }
+
+    for (pos, c) in change_set {
+        buf.setv(true, pos, c, symbols);
+    }
+}
+
+/// Returns the positions along the edges of `rect`.
+fn outline(rect: Rect) -> impl Iterator<Item = Vec2> {
+    let (left, top) = rect.top_left().pair();
+    let (right, bottom) = rect.bottom_right().pair();
+
+    (left..=right)
+        .flat_map(move |x| [Vec2::new(x, top), Vec2::new(x, bottom)])
+        .chain((top..=bottom).flat_map(move |y| [Vec2::new(left, y), Vec2::new(right, y)]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: [Transform; 3] = [Transform::Rotate, Transform::FlipH, Transform::FlipV];
+
+    fn transformed(text: &str, area: Rect, transform: Transform) -> String {
+        let mut buf = Buffer::read_from(text.as_bytes(), 8).unwrap();
+        transform_on_buffer(&mut buf, area, transform, &Symbols::default());
+        buf.flush_edits();
+        buf.iter("").collect()
+    }
+
+    #[test]
+    fn remap_cycles_back() {
+        let s = Symbols::default();
+        let glyphs = [
+            s.n, s.e, s.s, s.w, s.tlcorn, s.trcorn, s.brcorn, s.blcorn, s.lhinter, s.rhinter,
+            s.tvinter, s.bvinter, s.hline, s.vline, s.dash, s.pipe, s.diag, s.gaid, 'x',
+        ];
+
+        for c in glyphs {
+            let rotated = (0..4).fold(c, |c, _| Transform::Rotate.remap(c, &s));
+            assert_eq!(rotated, c);
+            for flip in [Transform::FlipH, Transform::FlipV] {
+                assert_eq!(flip.remap(flip.remap(c, &s), &s), c);
+            }
+        }
+
+        assert_eq!(Transform::Rotate.remap(s.n, &s), s.e);
+        assert_eq!(Transform::Rotate.remap(s.tlcorn, &s), s.trcorn);
+        assert_eq!(Transform::FlipH.remap(s.diag, &s), s.gaid);
+        assert_eq!(Transform::FlipV.remap(s.w, &s), s.w);
+    }
+
+    #[test]
+    fn positions_stay_within_the_new_rect() {
+        let area = Rect::from_corners((2, 1), (5, 2));
+
+        for transform in ALL {
+            let target = transform.apply_rect(area);
+            for x in 2..=5 {
+                for y in 1..=2 {
+                    let pos = transform.apply_pos(area, Vec2::new(x, y), 1).unwrap();
+                    assert!(target.contains(pos), "{} moved {:?} to {:?}", transform, (x, y), pos);
+                }
+            }
+        }
+
+        assert_eq!(Transform::Rotate.apply_pos(area, Vec2::new(2, 1), 1), Some(Vec2::new(3, 1)));
+        assert_eq!(Transform::FlipH.apply_pos(area, Vec2::new(2, 1), 2), Some(Vec2::new(4, 1)));
+        assert_eq!(Transform::FlipV.apply_pos(area, Vec2::new(2, 1), 1), Some(Vec2::new(2, 2)));
+    }
+
+    #[test]
+    fn wide_glyphs_stay_whole() {
+        let area = Rect::from_corners((0, 0), (3, 0));
+        assert_eq!(transformed("中ab\n", area, Transform::FlipH), "ba中\n");
+        assert_eq!(transformed("e\u{301}中\n", Rect::from_corners((0, 0), (2, 0)), Transform::FlipH), "中e\u{301}\n");
+    }
+
+    #[test]
+    fn rotate_turns_arrows() {
+        let area = Rect::from_corners((0, 0), (2, 0));
+        assert_eq!(transformed("──▶\n", area, Transform::Rotate), "│  \n│\n▼\n");
+    }
+}
@@ -1,15 +1,17 @@
 use cursive::{
     align::HAlign,
-    view::{scroll::Scroller, Nameable, View},
-    views::{Dialog, EditView, ScrollView, TextView},
+    view::{scroll::Scroller, Nameable, Scrollable, View},
+    views::{Dialog, EditView, ScrollView, SelectView, TextView},
     Cursive,
 };
+use std::fs;
 use std::sync::Arc;
 use std::path::PathBuf;
 
 use crate::constants::{EDITOR_ID, NO_MARGIN, POPUP_ID, INPUT_ID};
-use crate::editor::{Editor, EditorView};
+use crate::editor::{embed, history, Editor, EditorView};
 use crate::config::Options;
+use crate::export::Format;
 use crate::tools::Tool;
 
 /// Run `f` if the editor's buffer has not been modified since the last save, or if user
@@ -173,7 +175,7 @@ pub(crate) fn editor_open(siv: &mut Cursive) {
                 return;
             }
 
-            let path: PathBuf = raw_path.into();
+            let (path, line) = embed::split_location(raw_path.as_ref());
             if !path.exists() {
                 view.set_title(format!("Open: {:?} does not exist!", path));
                 return;
@@ -184,13 +186,64 @@ pub(crate) fn editor_open(siv: &mut Cursive) {
             }
             siv.pop_layer();
 
-            if let Err(e) = with_editor_mut(siv, |e| e.open_file(path)) {
+            let res = match line {
+                Some(line) => with_editor_mut(siv, |e| e.open_embedded(path, line)),
+                None => with_editor_mut(siv, |e| e.open_file(path)),
+            };
+            if let Err(e) = res {
                 notify(siv, "open failed", format!("{:?}", e));
             }
         })
     });
 }
 
+/// Prompt for a file, then list the fenced blocks in it and open whichever one is
+/// submitted as an embedded diagram.
+pub(crate) fn editor_open_block(siv: &mut Cursive) {
+    with_checked_editor(siv, "Open Block", |siv| {
+        display_form(siv, "Open Block", |siv, id, raw_path| {
+            let mut view = siv.find_name::<Dialog>(id).unwrap();
+
+            let path = PathBuf::from(raw_path);
+            let host = match fs::read_to_string(&path) {
+                Ok(host) => host,
+                Err(e) => {
+                    view.set_title(format!("Open Block: {}", e));
+                    return;
+                }
+            };
+
+            let blocks = embed::fenced_block_list(&host);
+            if blocks.is_empty() {
+                view.set_title(format!("Open Block: no fenced blocks in {:?}!", path));
+                return;
+            }
+            drop(view);
+            siv.pop_layer();
+
+            let mut list = SelectView::new();
+            for (line, preview) in blocks {
+                list.add_item(format!("{:>5}  {}", line, preview), line);
+            }
+
+            list.set_on_submit(move |siv, &line: &usize| {
+                siv.pop_layer();
+                if let Err(e) = with_editor_mut(siv, |e| e.open_embedded(&path, line)) {
+                    notify(siv, "open failed", format!("{:?}", e));
+                }
+            });
+
+            let popup = Dialog::around(list.scrollable())
+                .title("Open Block")
+                .dismiss_button("Cancel")
+                .padding(NO_MARGIN)
+                .with_name(POPUP_ID);
+
+            siv.add_layer(popup);
+        })
+    });
+}
+
 pub(crate) fn editor_save(siv: &mut Cursive) {
     match with_editor_mut(siv, Editor::save).map_err(|e| format!("{:?}", e)) {
         Ok(false) => editor_save_as(siv),
@@ -240,6 +293,45 @@ pub(crate) fn editor_clip_prefix(siv: &mut Cursive) {
     });
 }
 
+pub(crate) fn editor_paste(siv: &mut Cursive) {
+    match with_editor_mut(siv, Editor::paste_from_clipboard).map_err(|e| format!("{:?}", e)) {
+        Ok(true) => {}
+        Ok(false) => notify(siv, "nothing to paste", ""),
+        Err(e) => notify(siv, "paste failed", e),
+    }
+}
+
+pub(crate) fn editor_put(siv: &mut Cursive) {
+    if !with_editor_mut(siv, Editor::put) {
+        notify(siv, "register is empty", "");
+    }
+}
+
+pub(crate) fn editor_export_svg(siv: &mut Cursive) {
+    editor_export(siv, "Export SVG", Format::Svg);
+}
+
+pub(crate) fn editor_export_html(siv: &mut Cursive) {
+    editor_export(siv, "Export HTML", Format::Html);
+}
+
+/// Prompt for a path and export the buffer there as `format`.
+fn editor_export(siv: &mut Cursive, title: &'static str, format: Format) {
+    display_form(siv, title, move |siv, id, raw_path| {
+        if raw_path.is_empty() {
+            let mut view = siv.find_name::<Dialog>(id).unwrap();
+            view.set_title(format!("{}: path is empty!", title));
+            return;
+        }
+        siv.pop_layer();
+
+        match with_editor(siv, |e| e.export_to(raw_path, format)).map_err(|e| format!("{:?}", e)) {
+            Ok(()) => notify(siv, "exported", ""),
+            Err(e) => notify(siv, "export failed", e),
+        }
+    });
+}
+
 pub(crate) fn editor_quit(siv: &mut Cursive) {
     with_checked_editor(siv, "Quit", Cursive::quit);
 }
@@ -252,6 +344,68 @@ pub(crate) fn editor_redo(siv: &mut Cursive) {
     with_editor_mut(siv, Editor::redo);
 }
 
+pub(crate) fn editor_undo_older(siv: &mut Cursive) {
+    with_editor_mut(siv, Editor::undo_older);
+}
+
+pub(crate) fn editor_undo_newer(siv: &mut Cursive) {
+    with_editor_mut(siv, Editor::undo_newer);
+}
+
+/// Display every state in the undo tree, oldest first, and jump to whichever one is
+/// submitted.
+pub(crate) fn editor_undo_list(siv: &mut Cursive) {
+    if siv.find_name::<Dialog>(POPUP_ID).is_some() {
+        return;
+    }
+
+    let now = history::now();
+    let states = with_editor(siv, Editor::undo_states);
+
+    let mut list = SelectView::new();
+    let mut current = 0;
+    for (i, state) in states.iter().enumerate() {
+        if state.current {
+            current = i;
+        }
+
+        let label = format!(
+            "{} {:>4}  {:>8}  {:<16} {:>6}",
+            if state.current { '>' } else { ' ' },
+            state.seq,
+            time_ago(now, state.time),
+            state.desc,
+            state.changes,
+        );
+        list.add_item(label, state.seq);
+    }
+    list.set_selection(current);
+
+    list.set_on_submit(|siv, &seq: &usize| {
+        siv.pop_layer();
+        with_editor_mut(siv, |e| e.undo_jump(seq));
+    });
+
+    let popup = Dialog::around(list.scrollable())
+        .title("Undo Tree")
+        .dismiss_button("Cancel")
+        .padding(NO_MARGIN)
+        .with_name(POPUP_ID);
+
+    siv.add_layer(popup);
+}
+
+/// Returns a short human readable description of how long before `now` the unix
+/// timestamp `then` was.
+fn time_ago(now: u64, then: u64) -> String {
+    match now.saturating_sub(then) {
+        s @ 0..=59 => format!("{}s ago", s),
+        s @ 60..=3599 => format!("{}m ago", s / 60),
+        s @ 3600..=86399 => format!("{}h ago", s / 3600),
+        s => format!("{}d ago", s / 86400),
+    }
+}
+
 pub(crate) fn editor_trim_margins(siv: &mut Cursive) {
     with_editor_mut(siv, Editor::trim_margins);
     notify(siv, "trimmed", "");
@@ -279,28 +433,49 @@ where
 const HELP: &str = "KEYBINDS:
     Esc Focus the menu bar.
     n   New: Open a new (blank) file.
-    o   Open: Open the specified file.
+    o   Open: Open the specified file, or only the diagram at file:LINE.
+    O   Open Block: Pick a fenced block from the specified file to edit.
     w   Save: Save buffer to the current path.
     S   Save As: Save buffer to the specified path.
     c   Clip: Export buffer to the clipboard.
     C   Clip Prefix: Export buffer to the clipboard with a prefix before each line.
+    V   Export SVG: Export buffer to the specified path as an SVG image.
+    H   Export HTML: Export buffer to the specified path as an HTML document (background and normal text colours only).
     `   Debug: Open the debug console.
     q   Quit: Quit without saving.
     u   Undo: Undo the last buffer modification.
     Ctrl+r Redo: Redo the last undo.
+    Ctrl+v Paste: Paste the clipboard at the cursor, then move it into place.
+    P   Put: Put the yanked register at the cursor, then move it into place.
+    \"x  Use register x for the next yank or put.
+    -   Older State: Go back in time through every undo state, across branches.
+    +   Newer State: Go forward in time through every undo state, across branches.
+    U   Undo Tree: List every undo state and jump to the selected one.
     T   Trim Margins: Trim excess whitespace from all margins.
     s   Switch to Select mode.
+    v   Select the label or box under the cursor.
+    x   Delete the line, label or box under the cursor.
+    R   Resize the box under the cursor by the edge or corner it is on.
     b   Switch to the Box tool (enters Box Mode).
     L   Switch to the Line tool.
     a   Switch to the Arrow tool (enters Arrow Mode).
+    W   Draw a line through several waypoints (enters Polyline Mode).
     p   Cycle the type of path that Line and Arrow tools will draw.
-    t   Switch to the Text tool (enters Text Mode).
+    t   Switch to the Text tool (enters Text Mode). Inside a box, text wraps to fit.
+    B   Type a label, with a box drawn around it (enters Text Mode).
     ?   Help: Display this help message.
 
 MODES:
     Select Mode Actions:
         e   Erase selected content.
+        y   Yank selected content into a register.
+        v   Grow the selection to the box around it.
         m   Enter Move mode to move selected content.
+        M   Enter Move mode, re-routing lines attached to selected content.
+        d   Enter Move mode with a duplicate of selected content.
+        r   Rotate selected content 90 degrees clockwise.
+        f   Flip selected content horizontally.
+        F   Flip selected content vertically.
         s   Finish selection and return to Normal mode.
         Esc Exit Select mode.
 
@@ -309,6 +484,29 @@ MODES:
         Enter Commit move and return to Normal mode.
         Esc Finish move and return to Normal mode.
 
+    Resize Mode Actions:
+        hjkl Move the grabbed edge or corner.
+        Enter/Esc Commit the new size and return to Normal mode.
+
+    Polyline Mode Actions:
+        Click/Enter Add a waypoint at the cursor.
+        hjkl Move the cursor to place the next waypoint.
+        Backspace Remove the last waypoint.
+        a   Toggle an arrow tip at the end of the line.
+        Esc/Double-click Finish the line and return to Normal mode.
+
+    Text Mode Actions:
+        Tab Cycle the alignment between left, centre and right.
+        Home/End Move to the start or end of the line.
+        Ctrl+Left/Right Move by a word.
+        Del Delete the character after the cursor.
+        Ins Toggle between inserting and replacing characters.
+        Click on existing text to edit it in place.
+
+    Line/Arrow Mode Actions:
+        r   Cycle the type of path.
+        f   Flip a right angled line between going horizontally or vertically first.
+
     Box/Arrow/Text Modes:
         Enter/Esc Commit changes and return to Normal mode.
 
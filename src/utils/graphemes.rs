@@ -0,0 +1,88 @@
+use std::iter;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::editor::cell::Glyph;
+
+/// Returns the glyph for the single grapheme cluster `g`.
+fn glyph(g: &str) -> Glyph {
+    let mut cs = g.chars();
+    match (cs.next(), cs.next()) {
+        (Some(c), None) => Glyph::Char(c),
+        (None, _) => Glyph::Char(' '),
+        _ => Glyph::Cluster(g.into()),
+    }
+}
+
+/// Returns the number of columns `g` occupies on screen.
+///
+/// [Glyph::Wide] takes no columns of its own, everything else takes one or two.
+pub(crate) fn width(g: &Glyph) -> usize {
+    let w = match g {
+        Glyph::Char(c) => c.width().unwrap_or(1),
+        Glyph::Cluster(s) => s.width(),
+        Glyph::Wide => return 0,
+    };
+
+    w.clamp(1, 2)
+}
+
+/// Returns the buffer cells that display `s`, one per column. Each grapheme cluster
+/// becomes a single cell, followed by [Glyph::Wide] if it is double width.
+pub(crate) fn cells(s: &str) -> impl Iterator<Item = Glyph> + '_ {
+    s.graphemes(true).flat_map(|g| {
+        let g = glyph(g);
+        let wide = (width(&g) == 2).then_some(Glyph::Wide);
+        iter::once(g).chain(wide)
+    })
+}
+
+/// Returns the glyph for the single grapheme cluster formed by appending `next` to `g`,
+/// or `None` if `next` starts a new cluster.
+pub(crate) fn join(g: &Glyph, next: char) -> Option<Glyph> {
+    let mut s = g.to_string();
+    s.push(next);
+
+    let mut gs = s.graphemes(true);
+    match (gs.next(), gs.next()) {
+        (Some(g), None) => Some(glyph(g)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_cell_per_column() {
+        let cells: Vec<_> = cells("a中e\u{301}👍🏽").collect();
+        assert_eq!(cells, [
+            Glyph::Char('a'),
+            Glyph::Char('中'),
+            Glyph::Wide,
+            Glyph::Cluster("e\u{301}".into()),
+            Glyph::Cluster("👍🏽".into()),
+            Glyph::Wide,
+        ]);
+        assert_eq!(cells.iter().map(width).sum::<usize>(), cells.len());
+    }
+
+    #[test]
+    fn private_use_code_points_are_plain_chars() {
+        for c in ['\u{e000}', '\u{f0000}', '\u{10fffd}'] {
+            let s = c.to_string();
+            assert_eq!(cells(&s).collect::<Vec<_>>(), [Glyph::Char(c)]);
+        }
+    }
+
+    #[test]
+    fn join_extends_clusters() {
+        assert_eq!(join(&Glyph::Char('e'), '\u{301}'), Some(Glyph::Cluster("e\u{301}".into())));
+        assert_eq!(join(&Glyph::Char('e'), 'f'), None);
+        assert_eq!(
+            join(&Glyph::Cluster("👨\u{200d}".into()), '👩'),
+            Some(Glyph::Cluster("👨\u{200d}👩".into())),
+        );
+    }
+}
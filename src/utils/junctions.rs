@@ -1,5 +1,5 @@
 use crate::config::Symbols;
-use crate::editor::buffer::Buffer;
+use crate::editor::{buffer::Buffer, cell::Char};
 use cursive::Vec2;
 
 /// Returns true if the character provides a connection point on its BOTTOM edge (pointing South).
@@ -88,3 +88,26 @@ pub fn fixup_point(pos: Vec2, buf: &Buffer, symbols: &Symbols) -> char {
 
     smart
 }
+
+/// Repair every junction in `buf`, so that box-drawing characters join up with their
+/// neighbours. The repairs are left as pending edits.
+pub fn fixup_all(buf: &mut Buffer, symbols: &Symbols) {
+    let bounds = buf.bounds();
+    let joinable: Vec<_> = buf
+        .iter_within(Vec2::zero(), bounds, symbols)
+        .filter_map(|c| match c {
+            Char::Clean(cell) if is_joinable(cell.c(), symbols) => Some(cell.pos()),
+            _ => None,
+        })
+        .collect();
+
+    let change_set: Vec<_> = joinable
+        .into_iter()
+        .map(|pos| (pos, fixup_point(pos, buf, symbols)))
+        .filter(|&(pos, c)| c != buf.get_char_at(pos))
+        .collect();
+
+    for (pos, c) in change_set {
+        buf.setv(true, pos, c, symbols);
+    }
+}
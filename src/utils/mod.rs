@@ -1,3 +1,5 @@
+pub mod graphemes;
 pub mod ordfloat;
 pub mod rectedges;
 pub mod junctions;
+pub mod shapes;
@@ -16,7 +16,7 @@ impl Ord for OrdFloat {
 impl PartialOrd for OrdFloat {
     #[inline]
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        self.0.partial_cmp(&other.0)
+        Some(self.cmp(other))
     }
 }
 
@@ -3,7 +3,6 @@ use std::collections::HashSet;
 use cursive::{Rect, Vec2};
 
 pub struct RectEdges {
-    pub rect: Rect,
     pub coordinate_outline: HashSet<(usize, usize)>
 }
 
@@ -16,7 +15,6 @@ impl RectEdges {
         let right = collect_edges_y(rect.bottom_right(), rect.top_right());
 
         RectEdges {
-            rect,
             coordinate_outline: top.union(&bottom)
                 .chain(left.iter())
                 .chain(right.iter())
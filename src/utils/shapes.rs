@@ -0,0 +1,382 @@
+use cursive::{Rect, Vec2};
+use std::collections::{BTreeSet, HashSet, VecDeque};
+
+use crate::config::Symbols;
+use crate::constants::{S_N, S_E, S_S, S_W};
+use crate::editor::{buffer::Buffer, cell::{Char, Glyph}};
+use crate::utils::{graphemes, junctions::*};
+
+/// A rectangle outlined with box-drawing characters.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct BoxShape {
+    pub rect: Rect,
+}
+
+/// A connected run of line characters that is not part of a box outline.
+#[derive(Clone, Debug)]
+pub(crate) struct Polyline {
+    pub cells: Vec<Vec2>,
+    /// Cells joined to only one other cell of the line, and whether each one is an
+    /// arrow tip.
+    pub ends: Vec<(Vec2, bool)>,
+}
+
+/// A run of text on one row, with words separated by single spaces.
+#[derive(Clone, Debug)]
+pub(crate) struct Label {
+    pub pos: Vec2,
+    /// Number of columns the text occupies.
+    pub width: usize,
+    /// Index into `Shapes::boxes` of the smallest box the label is inside of.
+    pub parent: Option<usize>,
+}
+
+/// The boxes, lines and labels recognised in a buffer.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Shapes {
+    pub boxes: Vec<BoxShape>,
+    pub lines: Vec<Polyline>,
+    pub labels: Vec<Label>,
+}
+
+impl BoxShape {
+    /// Returns `true` if `pos` is within the outline.
+    pub(crate) fn inside(&self, pos: Vec2) -> bool {
+        let (left, top) = self.rect.top_left().pair();
+        let (right, bottom) = self.rect.bottom_right().pair();
+
+        left < pos.x && pos.x < right && top < pos.y && pos.y < bottom
+    }
+
+    /// Returns the positions along the outline.
+    pub(crate) fn outline(&self) -> impl Iterator<Item = Vec2> {
+        let (left, top) = self.rect.top_left().pair();
+        let (right, bottom) = self.rect.bottom_right().pair();
+
+        (left..=right)
+            .flat_map(move |x| [Vec2::new(x, top), Vec2::new(x, bottom)])
+            .chain((top + 1..bottom).flat_map(move |y| [Vec2::new(left, y), Vec2::new(right, y)]))
+    }
+}
+
+impl Label {
+    /// Returns the cells the label occupies.
+    pub(crate) fn rect(&self) -> Rect {
+        Rect::from_size(self.pos, (self.width, 1))
+    }
+
+    /// Returns the positions of the label's cells.
+    pub(crate) fn cells(&self) -> impl Iterator<Item = Vec2> {
+        let pos = self.pos;
+        (0..self.width).map(move |x| pos.map_x(|left| left + x))
+    }
+
+    /// Returns `true` if `other` is on the row below and shares a column with it.
+    fn stacks_on(&self, other: &Label) -> bool {
+        other.pos.y + 1 == self.pos.y
+            && self.parent == other.parent
+            && self.pos.x < other.pos.x + other.width
+            && other.pos.x < self.pos.x + self.width
+    }
+}
+
+impl Shapes {
+    /// Recognise the shapes drawn on `buf`, including any pending edits.
+    pub(crate) fn scan(buf: &Buffer, symbols: &Symbols) -> Self {
+        let visible: BTreeSet<_> = buf
+            .iter_within(Vec2::zero(), buf.bounds(), symbols)
+            .filter_map(|c| match c {
+                Char::Clean(cell) | Char::Dirty(cell) => Some(cell.pos()),
+                _ => None,
+            })
+            .filter(|&pos| buf.visible(pos))
+            .map(|pos| (pos.y, pos.x))
+            .collect();
+
+        let visible: Vec<_> = visible.into_iter().map(|(y, x)| Vec2::new(x, y)).collect();
+
+        let boxes = find_boxes(buf, &visible, symbols);
+        let outlines: HashSet<_> = boxes.iter().flat_map(BoxShape::outline).collect();
+
+        let (line_cells, text_cells): (Vec<_>, Vec<_>) = visible
+            .into_iter()
+            .filter(|pos| !outlines.contains(pos))
+            .partition(|&pos| is_line(buf.get_char_at(pos), symbols));
+
+        let lines = find_lines(buf, &line_cells, symbols);
+        let labels = find_labels(buf, &text_cells, &boxes);
+
+        Self { boxes, lines, labels }
+    }
+
+    /// Returns the smallest box with `pos` on or within its outline.
+    pub(crate) fn box_at(&self, pos: Vec2) -> Option<&BoxShape> {
+        self.boxes
+            .iter()
+            .filter(|b| b.rect.contains(pos))
+            .min_by_key(|b| b.rect.width() * b.rect.height())
+    }
+
+    /// Returns the smallest box around `area`, other than one outlining it exactly.
+    pub(crate) fn box_around(&self, area: Rect) -> Option<&BoxShape> {
+        self.boxes
+            .iter()
+            .filter(|b| b.rect != area && b.rect.contains(area.top_left()) && b.rect.contains(area.bottom_right()))
+            .min_by_key(|b| b.rect.width() * b.rect.height())
+    }
+
+    /// Returns the label that covers `pos`.
+    pub(crate) fn label_at(&self, pos: Vec2) -> Option<&Label> {
+        self.labels.iter().find(|label| label.rect().contains(pos))
+    }
+
+    /// Returns the labels on consecutive rows, stacked on one another, that make up the
+    /// block of text at `pos`, from top to bottom.
+    pub(crate) fn block_at(&self, pos: Vec2) -> Vec<&Label> {
+        let mut block: VecDeque<_> = self.label_at(pos).into_iter().collect();
+
+        while let Some(above) = block.front().and_then(|&first| self.labels.iter().find(|l| first.stacks_on(l))) {
+            block.push_front(above);
+        }
+        while let Some(below) = block.back().and_then(|&last| self.labels.iter().find(|l| l.stacks_on(last))) {
+            block.push_back(below);
+        }
+
+        block.into()
+    }
+
+    /// Returns the line that passes through `pos`.
+    pub(crate) fn line_at(&self, pos: Vec2) -> Option<&Polyline> {
+        self.lines.iter().find(|line| line.cells.contains(&pos))
+    }
+
+    /// Returns the labels inside the box at index `i` of `boxes`.
+    pub(crate) fn labels_in(&self, i: usize) -> impl Iterator<Item = &Label> {
+        self.labels.iter().filter(move |label| label.parent == Some(i))
+    }
+}
+
+/// Returns whether `c` is drawn as part of a line.
+fn is_line(c: char, s: &Symbols) -> bool {
+    is_joinable(c, s) || c == s.diag2 || c == s.gaid2
+}
+
+/// Returns whether `c` joins up with its neighbour in direction `dir`.
+fn connects(c: char, dir: (isize, isize), s: &Symbols) -> bool {
+    match dir {
+        S_N => connects_up(c, s),
+        S_E => connects_right(c, s),
+        S_S => connects_down(c, s),
+        _ => connects_left(c, s),
+    }
+}
+
+/// Returns every rectangle with a top left corner among `cells`. A box split by
+/// dividers is found as each of its parts.
+fn find_boxes(buf: &Buffer, cells: &[Vec2], s: &Symbols) -> Vec<BoxShape> {
+    let at = |x, y| buf.get_char_at(Vec2::new(x, y));
+    let mut boxes = Vec::new();
+
+    for &Vec2 { x: left, y: top } in cells {
+        let c = at(left, top);
+        if !(connects_down(c, s) && connects_right(c, s)) {
+            continue;
+        }
+
+        let mut right = left + 1;
+        while connects_left(at(right, top), s) {
+            let c = at(right, top);
+
+            if connects_down(c, s) {
+                if let Some(bottom) = find_bottom(buf, left, top, right, s) {
+                    let rect = Rect::from_corners((left, top), (right, bottom));
+                    boxes.push(BoxShape { rect });
+                }
+            }
+
+            if !connects_right(c, s) {
+                break;
+            }
+            right += 1;
+        }
+    }
+
+    boxes
+}
+
+/// Follow the left and right edges of a box down from `top`, returning the first row
+/// that closes it with a complete bottom edge.
+fn find_bottom(buf: &Buffer, left: usize, top: usize, right: usize, s: &Symbols) -> Option<usize> {
+    let at = |x, y| buf.get_char_at(Vec2::new(x, y));
+
+    let mut y = top + 1;
+    loop {
+        let (l, r) = (at(left, y), at(right, y));
+        if !(connects_up(l, s) && connects_up(r, s)) {
+            return None;
+        }
+
+        let closed = connects_right(l, s)
+            && connects_left(r, s)
+            && (left + 1..right).all(|x| connects_left(at(x, y), s) && connects_right(at(x, y), s));
+
+        if closed {
+            return Some(y);
+        }
+
+        if !(connects_down(l, s) && connects_down(r, s)) {
+            return None;
+        }
+        y += 1;
+    }
+}
+
+/// Returns the cells that the line cell at `pos` is joined to.
+fn links(buf: &Buffer, cells: &HashSet<Vec2>, pos: Vec2, s: &Symbols) -> Vec<Vec2> {
+    let c = buf.get_char_at(pos);
+
+    let straight = [S_N, S_E, S_S, S_W].into_iter().filter_map(|dir| {
+        let next = pos.checked_add(dir)?;
+        let joined = connects(c, dir, s) && connects(buf.get_char_at(next), (-dir.0, -dir.1), s);
+        joined.then_some(next)
+    });
+
+    // diagonals join anything on the line at either of their ends
+    let diagonal = [((1, -1), s.diag2), ((-1, 1), s.diag2), ((-1, -1), s.gaid2), ((1, 1), s.gaid2)]
+        .into_iter()
+        .filter_map(|(dir, diag)| {
+            let next = pos.checked_add(dir)?;
+            (c == diag || buf.get_char_at(next) == diag).then_some(next)
+        });
+
+    straight.chain(diagonal).filter(|next| cells.contains(next)).collect()
+}
+
+/// Group `cells` into connected lines.
+fn find_lines(buf: &Buffer, cells: &[Vec2], s: &Symbols) -> Vec<Polyline> {
+    let all: HashSet<_> = cells.iter().copied().collect();
+    let mut seen = HashSet::new();
+    let mut lines = Vec::new();
+
+    for &first in cells {
+        if !seen.insert(first) {
+            continue;
+        }
+
+        let mut line = Polyline { cells: Vec::new(), ends: Vec::new() };
+        let mut queue = VecDeque::from([first]);
+
+        while let Some(pos) = queue.pop_front() {
+            let next = links(buf, &all, pos, s);
+            if next.len() <= 1 {
+                line.ends.push((pos, is_arrow_tip(buf.get_char_at(pos), s)));
+            }
+
+            line.cells.push(pos);
+            queue.extend(next.into_iter().filter(|&p| seen.insert(p)));
+        }
+
+        lines.push(line);
+    }
+
+    lines
+}
+
+/// Group `cells`, which are in row order, into labels.
+fn find_labels(buf: &Buffer, cells: &[Vec2], boxes: &[BoxShape]) -> Vec<Label> {
+    let mut labels: Vec<Label> = Vec::new();
+
+    for &pos in cells {
+        let g = buf.glyph_at(pos);
+        if g == Glyph::Wide {
+            continue;
+        }
+
+        match labels.last_mut() {
+            Some(label) if label.pos.y == pos.y && pos.x <= label.pos.x + label.width + 1 => {
+                label.width = pos.x + graphemes::width(&g) - label.pos.x;
+            }
+            _ => labels.push(Label { pos, width: graphemes::width(&g), parent: None }),
+        }
+    }
+
+    for label in labels.iter_mut() {
+        let last = label.pos.map_x(|x| x + label.width - 1);
+
+        label.parent = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| b.inside(label.pos) && b.inside(last))
+            .min_by_key(|(_, b)| b.rect.width() * b.rect.height())
+            .map(|(i, _)| i);
+    }
+
+    labels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIAGRAM: &str = "\
+┌──────────┐
+│ ┌─┐  two │
+│ │a│ words│
+│ └─┘      │
+└──────────┘
+ ───┐
+    └──▶ 中 text
+";
+
+    fn scan(text: &str) -> Shapes {
+        let buf = Buffer::read_from(text.as_bytes(), 8).unwrap();
+        Shapes::scan(&buf, &Symbols::default())
+    }
+
+    #[test]
+    fn nested_boxes() {
+        let shapes = scan(DIAGRAM);
+        let rects: Vec<_> = shapes.boxes.iter().map(|b| b.rect).collect();
+        assert_eq!(rects, [Rect::from_corners((0, 0), (11, 4)), Rect::from_corners((2, 1), (4, 3))]);
+
+        assert_eq!(shapes.box_at(Vec2::new(3, 2)), Some(&shapes.boxes[1]));
+        assert_eq!(shapes.box_at(Vec2::new(8, 2)), Some(&shapes.boxes[0]));
+        assert_eq!(shapes.box_around(shapes.boxes[1].rect), Some(&shapes.boxes[0]));
+        assert_eq!(shapes.box_around(shapes.boxes[0].rect), None);
+    }
+
+    #[test]
+    fn lines_and_arrow_tips() {
+        let shapes = scan(DIAGRAM);
+        assert_eq!(shapes.lines.len(), 1);
+
+        let line = shapes.line_at(Vec2::new(4, 5)).unwrap();
+        assert_eq!(line.cells.len(), 8);
+
+        let mut ends = line.ends.clone();
+        ends.sort_by_key(|&(pos, _)| (pos.y, pos.x));
+        assert_eq!(ends, [(Vec2::new(1, 5), false), (Vec2::new(7, 6), true)]);
+    }
+
+    #[test]
+    fn labels_in_boxes() {
+        let shapes = scan(DIAGRAM);
+
+        let a = shapes.label_at(Vec2::new(3, 2)).unwrap();
+        assert_eq!((a.pos, a.width, a.parent), (Vec2::new(3, 2), 1, Some(1)));
+
+        let block: Vec<_> = shapes.block_at(Vec2::new(9, 1)).iter().map(|l| l.rect()).collect();
+        assert_eq!(block, [Rect::from_size((7, 1), (3, 1)), Rect::from_size((6, 2), (5, 1))]);
+        assert_eq!(shapes.labels_in(0).count(), 2);
+
+        let outside = shapes.label_at(Vec2::new(13, 6)).unwrap();
+        assert_eq!((outside.pos, outside.width, outside.parent), (Vec2::new(9, 6), 7, None));
+    }
+
+    #[test]
+    fn open_outlines_are_not_boxes() {
+        let shapes = scan("┌──┐\n│  │\n└── \n");
+        assert!(shapes.boxes.is_empty());
+        assert_eq!(shapes.lines.len(), 1);
+    }
+}